@@ -8,17 +8,22 @@
 use crate::alloc::alloc::{alloc, dealloc, Layout};
 use crate::alloc::boxed::Box;
 use crate::alloc::{vec, vec::Vec};
-use core::any::{type_name, TypeId};
-use core::hash::{BuildHasher, BuildHasherDefault, Hasher};
-use core::ops::Deref;
+use core::any::{type_name, Any, TypeId};
+use core::hash::{BuildHasher, BuildHasherDefault, Hash, Hasher};
+use core::mem::{self, MaybeUninit};
+use core::ops::{Deref, DerefMut, Range};
 use core::ptr::{self, NonNull};
+use core::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 use core::{fmt, slice};
 
 use hashbrown::{hash_map::DefaultHashBuilder, HashMap};
 
 use crate::borrow::AtomicBorrow;
+use crate::bundle::{sort_type_info, DuplicateComponent};
 use crate::query::Fetch;
-use crate::{Access, Component, Query};
+#[cfg(feature = "serde")]
+use crate::serialize::snapshot::{ArchetypeSnapshot, SnapshotError, SnapshotRegistry};
+use crate::{Access, Bundle, Component, DynamicClone, EntityBuilder, Query, Ref, RefMut};
 
 /// A collection of entities having the same component types
 ///
@@ -30,38 +35,82 @@ pub struct Archetype {
     index: OrderedTypeIdMap<usize>,
     len: u32,
     entities: Box<[u32]>,
+    /// Number of times each slot has been given a new entity, never shrinking
+    generations: Vec<u32>,
     /// One allocation per type, in the same order as `types`
     data: Box<[Data]>,
+    /// Incremented whenever any column's storage may have moved
+    realloc_generation: u64,
+    // AtomicU64 is unsupported on some 32-bit architectures, so only a word is offered
+    user_data: AtomicUsize,
+    frozen: AtomicBool,
+    /// Held by an `ArchetypeGuard`
+    lock: AtomicBorrow,
+    /// Indices of entities whose components were dropped by `mark_dead`, awaiting `compact`
+    dead: Vec<u32>,
+    /// In ring mode, the slot to be overwritten by the next allocation when full
+    ring_cursor: Option<u32>,
+    stable_order: bool,
+    /// Key of the bundle type most recently passed to `spawn_static`, and the state index of each
+    /// of its components in `put` order
+    static_layout: Option<(TypeId, Box<[usize]>)>,
+    #[cfg(feature = "metrics")]
+    counters: Counters,
+    /// For each entity, a bitmask of the columns which have been written
+    #[cfg(debug_assertions)]
+    initialized: Vec<u64>,
 }
 
 impl Archetype {
-    fn assert_type_info(types: &[TypeInfo]) {
-        types.windows(2).for_each(|x| match x[0].cmp(&x[1]) {
-            core::cmp::Ordering::Less => (),
-            #[cfg(debug_assertions)]
-            core::cmp::Ordering::Equal => panic!(
+    /// Maximum number of component types an archetype is designed to hold
+    ///
+    /// Exceeding this panics in debug builds, which track whether each component has been written
+    /// in a per-entity bitmask, and per-type loops and lookups are not optimized for very large
+    /// component sets either.
+    pub const MAX_COMPONENTS: usize = 64;
+
+    /// Per-entity storage size and maximum alignment of an archetype with component `types`
+    ///
+    /// Allows estimating the memory cost of a combination of components before any entity having
+    /// them exists. Each component type is stored in its own densely packed column, so the
+    /// per-entity size is simply the sum of the component sizes.
+    pub fn describe_layout(types: &[TypeInfo]) -> (usize, usize) {
+        types.iter().fold((0, 1), |(size, align), ty| {
+            (size + ty.layout.size(), align.max(ty.layout.align()))
+        })
+    }
+
+    fn duplicate_panic(err: DuplicateComponent) -> ! {
+        match err.name() {
+            Some(name) => panic!(
                 "attempted to allocate entity with duplicate {} components; \
                  each type must occur at most once!",
-                x[0].type_name
+                name
             ),
-            #[cfg(not(debug_assertions))]
-            core::cmp::Ordering::Equal => panic!(
+            None => panic!(
                 "attempted to allocate entity with duplicate components; \
                  each type must occur at most once!"
             ),
-            core::cmp::Ordering::Greater => panic!("type info is unsorted"),
-        });
+        }
     }
 
     pub(crate) fn new(types: Vec<TypeInfo>) -> Self {
+        let types = sort_type_info(types).unwrap_or_else(|err| Self::duplicate_panic(err));
         let max_align = types.first().map_or(1, |ty| ty.layout.align());
-        Self::assert_type_info(&types);
+        debug_assert!(
+            types.len() <= Self::MAX_COMPONENTS,
+            "archetype has {} component types, exceeding the limit of {}: {:?}",
+            types.len(),
+            Self::MAX_COMPONENTS,
+            TypeNames(&types)
+        );
         let component_count = types.len();
-        Self {
-            index: OrderedTypeIdMap::new(types.iter().enumerate().map(|(i, ty)| (ty.id, i))),
-            type_ids: types.iter().map(|ty| ty.id()).collect(),
+        let mut archetype = Self {
+            index: OrderedTypeIdMap::new(core::iter::empty()),
+            type_ids: Box::new([]),
             types,
             entities: Box::new([]),
+            generations: Vec::new(),
             len: 0,
             data: (0..component_count)
                 .map(|_| Data {
@@ -69,12 +118,71 @@ impl Archetype {
                     storage: NonNull::new(max_align as *mut u8).unwrap(),
                 })
                 .collect(),
-        }
+            realloc_generation: 0,
+            user_data: AtomicUsize::new(0),
+            frozen: AtomicBool::new(false),
+            lock: AtomicBorrow::new(),
+            dead: Vec::new(),
+            ring_cursor: None,
+            stable_order: false,
+            static_layout: None,
+            #[cfg(feature = "metrics")]
+            counters: Counters::default(),
+            #[cfg(debug_assertions)]
+            initialized: Vec::new(),
+        };
+        archetype.rebuild_index();
+        archetype
+    }
+
+    /// Construct an archetype which overwrites its oldest entities rather than growing past `cap`
+    ///
+    /// When full, each insertion drops the components of the entity in the next slot in turn and
+    /// reuses it, so the steady state never allocates. Slots are initially filled in order, so this
+    /// replaces the oldest entity so long as none are removed. Insertions report the ID of any
+    /// entity they evict, and capacity never changes: reserving space beyond `cap` panics, and
+    /// other means of growing or shrinking storage have no effect.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `cap` is 0 or `types` contains a type more than once.
+    pub fn with_ring_capacity(types: Vec<TypeInfo>, cap: u32) -> Self {
+        assert!(cap != 0, "ring capacity must be nonzero");
+        let mut archetype = Self::new(types);
+        archetype.set_capacity(cap as usize);
+        archetype.ring_cursor = Some(0);
+        archetype
+    }
+
+    /// Sort columns into storage order and rebuild the type lookup tables, preserving all data
+    ///
+    /// Must be called whenever the set of component types changes.
+    fn rebuild_index(&mut self) {
+        let types = mem::take(&mut self.types);
+        let data = mem::take(&mut self.data).into_vec();
+        let mut columns = types.into_iter().zip(data).collect::<Vec<_>>();
+        columns.sort_unstable_by_key(|x| x.0);
+        let (types, data): (Vec<_>, Vec<_>) = columns.into_iter().unzip();
+        self.index = OrderedTypeIdMap::new(types.iter().enumerate().map(|(i, ty)| (ty.id, i)));
+        self.type_ids = types.iter().map(|ty| ty.id()).collect();
+        self.types = types;
+        self.data = data.into_boxed_slice();
+        self.static_layout = None;
     }
 
     pub(crate) fn clear(&mut self) {
-        for (ty, data) in self.types.iter().zip(&*self.data) {
+        let mut dead = mem::take(&mut self.dead);
+        dead.sort_unstable();
+        // Skip searching for holes in the common case that `mark_dead` was never called
+        let has_holes = !dead.is_empty();
+        for (state, (ty, data)) in self.types.iter().zip(&*self.data).enumerate() {
             for index in 0..self.len {
+                // Panicking here could abort during unwinding, so just leak anything unwritten
+                if !self.is_initialized(state, index)
+                    || (has_holes && dead.binary_search(&index).is_ok())
+                {
+                    continue;
+                }
                 unsafe {
                     let removed = data.storage.as_ptr().add(index as usize * ty.layout.size());
                     (ty.drop)(removed);
@@ -82,6 +190,152 @@ impl Archetype {
             }
         }
         self.len = 0;
+        self.reset_ring_cursor();
+        #[cfg(debug_assertions)]
+        self.initialized.clear();
+    }
+
+    /// In ring mode, make the first slot the next to be overwritten, as after construction
+    ///
+    /// Must be called whenever the archetype is emptied, so that slots are refilled in order and
+    /// the oldest entity is again the first evicted.
+    fn reset_ring_cursor(&mut self) {
+        if self.ring_cursor.is_some() {
+            self.ring_cursor = Some(0);
+        }
+    }
+
+    /// Arbitrary application-defined metadata associated with this archetype
+    ///
+    /// Not interpreted by hecs in any way, and initially zero. Convenient for tooling to attach
+    /// flags or handles to archetypes without maintaining a separate map.
+    pub fn user_data(&self) -> usize {
+        self.user_data.load(Ordering::Relaxed)
+    }
+
+    /// Set the value returned by [`user_data`](Self::user_data)
+    pub fn set_user_data(&self, value: usize) {
+        self.user_data.store(value, Ordering::Relaxed);
+    }
+
+    /// Forbid structural changes, i.e. adding, removing, or moving entities, until
+    /// [`thaw`](Self::thaw) is called
+    ///
+    /// Violations panic in debug builds. Useful for catching entities being spawned or despawned
+    /// while pointers into the archetype are still in use. Independent of component borrows.
+    pub fn freeze(&self) {
+        self.frozen.store(true, Ordering::Relaxed);
+    }
+
+    /// Permit structural changes forbidden by [`freeze`](Self::freeze)
+    pub fn thaw(&self) {
+        self.frozen.store(false, Ordering::Relaxed);
+    }
+
+    /// Whether structural changes are currently forbidden by [`freeze`](Self::freeze)
+    pub fn is_frozen(&self) -> bool {
+        self.frozen.load(Ordering::Relaxed)
+    }
+
+    /// Whether any component type's storage is currently borrowed, shared or uniquely
+    pub fn any_borrowed(&self) -> bool {
+        self.data.iter().any(|x| x.state.is_borrowed())
+    }
+
+    /// Acquire exclusive access to the whole archetype, if no other [`ArchetypeGuard`] exists
+    ///
+    /// A cheaper alternative to borrowing individual columns for schedulers which hand whole
+    /// archetypes to different threads. The lock is advisory: it is independent of column borrows,
+    /// which remain necessary to actually access components.
+    pub fn try_lock(&self) -> Option<ArchetypeGuard<'_>> {
+        if self.lock.borrow_mut() {
+            Some(ArchetypeGuard { archetype: self })
+        } else {
+            None
+        }
+    }
+
+    fn debug_assert_thawed(&mut self) {
+        debug_assert!(
+            !*self.frozen.get_mut(),
+            "structural change to a frozen archetype"
+        );
+    }
+
+    /// Record that the `ty` component of the entity at `index` has been written
+    #[inline]
+    fn mark_initialized(&mut self, _ty: TypeId, _index: u32) {
+        #[cfg(debug_assertions)]
+        {
+            let state = *self.index.get(&_ty).unwrap();
+            self.initialized[_index as usize] |= 1 << state;
+        }
+    }
+
+    /// Whether the component in column `state` of the entity at `index` has been written
+    ///
+    /// Always `true` in release builds, where this isn't tracked.
+    #[inline]
+    fn is_initialized(&self, _state: usize, _index: u32) -> bool {
+        #[cfg(debug_assertions)]
+        {
+            self.initialized[_index as usize] & (1 << _state) != 0
+        }
+        #[cfg(not(debug_assertions))]
+        {
+            true
+        }
+    }
+
+    /// Panic if the components in column `state` of the entities at `start..end` might not have
+    /// been written
+    #[inline]
+    fn debug_assert_initialized(&self, _state: usize, _start: u32, _end: u32) {
+        #[cfg(debug_assertions)]
+        for index in _start.._end {
+            assert!(
+                self.is_initialized(_state, index),
+                "{} component of entity at index {} was never written",
+                self.types[_state].type_name,
+                index
+            );
+        }
+    }
+
+    /// Panic if any component of the entity at `index` might not have been written
+    #[inline]
+    fn debug_assert_entity_initialized(&self, _index: u32) {
+        #[cfg(debug_assertions)]
+        for state in 0..self.types.len() {
+            self.debug_assert_initialized(state, _index, _index + 1);
+        }
+    }
+
+    /// Bitmask with one bit set for each column
+    #[cfg(debug_assertions)]
+    fn all_columns(&self) -> u64 {
+        match self.types.len() {
+            64 => !0,
+            n => (1 << n) - 1,
+        }
+    }
+
+    /// Remove all entities without running any destructors
+    ///
+    /// The cheapest possible reset, intended for archetypes of plain-data components such as
+    /// particle pools which are refilled every frame. Components which need to be dropped are
+    /// leaked, which is reported in debug builds.
+    pub fn clear_fast(&mut self) {
+        debug_assert!(
+            self.types.iter().all(|ty| !ty.needs_drop),
+            "clear_fast would leak components which need to be dropped"
+        );
+        self.debug_assert_thawed();
+        self.len = 0;
+        self.dead.clear();
+        self.reset_ring_cursor();
+        #[cfg(debug_assertions)]
+        self.initialized.clear();
     }
 
     /// Whether this archetype contains `T` components
@@ -94,36 +348,495 @@ impl Archetype {
         self.index.contains_key(&id)
     }
 
+    /// Layout and destructor of the component type identified by `ty`, if present
+    pub fn type_info(&self, ty: TypeId) -> Option<&TypeInfo> {
+        Some(&self.types[*self.index.get(&ty)?])
+    }
+
+    /// Whether this archetype contains exactly the component types identified by `ids`
+    ///
+    /// Unlike [`has_dynamic`](Self::has_dynamic), additional component types cause this to return
+    /// `false`. `ids` must not contain duplicates.
+    pub fn has_exact_types(&self, ids: &[TypeId]) -> bool {
+        self.types.len() == ids.len() && ids.iter().all(|id| self.index.contains_key(id))
+    }
+
+    /// The subset of `requested` which this archetype does not contain, in order
+    ///
+    /// Useful for reporting which components prevented an entity from matching.
+    pub fn missing_types(&self, requested: &[TypeId]) -> Vec<TypeId> {
+        requested
+            .iter()
+            .copied()
+            .filter(|id| !self.index.contains_key(id))
+            .collect()
+    }
+
+    /// Component types stored only in `self` and only in `other`, respectively, in storage order
+    ///
+    /// Useful for determining which components must be added or removed to move an entity from
+    /// one archetype to another.
+    pub fn type_diff(&self, other: &Archetype) -> (Vec<TypeId>, Vec<TypeId>) {
+        let mut only_self = Vec::new();
+        let mut only_other = Vec::new();
+        let mut xs = self.types.iter().peekable();
+        let mut ys = other.types.iter().peekable();
+        loop {
+            match (xs.peek(), ys.peek()) {
+                (Some(x), Some(y)) => match x.cmp(y) {
+                    core::cmp::Ordering::Less => only_self.extend(xs.next().map(|x| x.id)),
+                    core::cmp::Ordering::Greater => only_other.extend(ys.next().map(|y| y.id)),
+                    core::cmp::Ordering::Equal => {
+                        xs.next();
+                        ys.next();
+                    }
+                },
+                (Some(_), None) => only_self.extend(xs.next().map(|x| x.id)),
+                (None, Some(_)) => only_other.extend(ys.next().map(|y| y.id)),
+                (None, None) => break,
+            }
+        }
+        (only_self, only_other)
+    }
+
     /// Find the state index associated with `T`, if present
     pub(crate) fn get_state<T: Component>(&self) -> Option<usize> {
-        self.index.get(&TypeId::of::<T>()).copied()
+        let state = self.index.get(&TypeId::of::<T>()).copied();
+        // Columns are added and removed at runtime, so guard against the index falling out of
+        // sync with the storage it describes
+        if let Some(state) = state {
+            debug_assert!(state < self.data.len());
+            debug_assert_eq!(self.types[state].id, TypeId::of::<T>());
+        }
+        state
     }
 
     /// Get the address of the first `T` component using an index from `get_state::<T>`
     pub(crate) fn get_base<T: Component>(&self, state: usize) -> NonNull<T> {
         assert_eq!(self.types[state].id, TypeId::of::<T>());
+        debug_assert!(state < self.data.len());
 
-        unsafe {
-            NonNull::new_unchecked(
-                self.data.get_unchecked(state).storage.as_ptr().cast::<T>() as *mut T
-            )
-        }
+        let ptr = unsafe { self.data.get_unchecked(state).storage.as_ptr().cast::<T>() };
+        // Only possible given a `TypeInfo` whose layout disagrees with `T`'s, but otherwise
+        // surfaces as undefined behavior far from the cause
+        debug_assert!(
+            ptr as usize % mem::align_of::<T>() == 0,
+            "{} column at state {} is misaligned",
+            type_name::<T>(),
+            state
+        );
+        unsafe { NonNull::new_unchecked(ptr) }
     }
 
     /// Get the `T` components of these entities, if present
     ///
     /// Useful for efficient serialization.
     pub fn get<T: Component>(&self) -> Option<ArchetypeColumn<'_, T>> {
-        let state = self.get_state::<T>()?;
+        self.column_range(0, self.len)
+    }
+
+    /// Clone the `T` components of these entities into an owned `Vec`
+    ///
+    /// Unlike a borrowed column, the copy can be kept indefinitely, e.g. for processing on a
+    /// background thread over several frames, without preventing the world from being modified.
+    /// Returns an empty `Vec` if `T` is not stored here.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the `T` components are uniquely borrowed.
+    pub fn snapshot_column<T: Component + Clone>(&self) -> Vec<T> {
+        self.get::<T>()
+            .map_or_else(Vec::new, |column| column.to_vec())
+    }
+
+    /// Count the entities having each distinct `T` value
+    ///
+    /// Returns an empty map if `T` is not stored here.
+    pub fn histogram<T: Component + Clone + Hash + Eq>(&self) -> HashMap<T, usize> {
+        let mut counts = HashMap::new();
+        if let Some(column) = self.get::<T>() {
+            for x in column.iter() {
+                match counts.get_mut(x) {
+                    Some(count) => *count += 1,
+                    None => {
+                        counts.insert(x.clone(), 1);
+                    }
+                }
+            }
+        }
+        counts
+    }
+
+    /// Index of the first entity whose `T` component satisfies `pred`, if any
+    ///
+    /// The index can be mapped to an entity ID using [`ids`](Self::ids).
+    pub fn find<T: Component>(&self, pred: impl Fn(&T) -> bool) -> Option<u32> {
+        let column = self.get::<T>()?;
+        column.iter().position(pred).map(|index| index as u32)
+    }
+
+    /// Number of entities whose `T` component satisfies `pred`, or 0 if `T` is not stored here
+    pub fn count_where<T: Component>(&self, pred: impl Fn(&T) -> bool) -> usize {
+        self.get::<T>()
+            .map_or(0, |column| column.iter().filter(|x| pred(x)).count())
+    }
+
+    /// Indices of these entities ordered by a key computed from their `T` components, if present
+    ///
+    /// Storage is left untouched, making this suitable for a per-pass ordering such as draw order.
+    /// The sort is stable.
+    pub fn sorted_indices_by<T: Component, K: Ord>(
+        &self,
+        key: impl Fn(&T) -> K,
+    ) -> Option<Vec<u32>> {
+        let column = self.get::<T>()?;
+        let mut indices = (0..self.len).collect::<Vec<_>>();
+        indices.sort_by_key(|&i| key(&column[i as usize]));
+        Some(indices)
+    }
+
+    /// Like [`get`](Self::get), but reports a conflicting unique borrow as an error rather than
+    /// panicking
+    ///
+    /// Suitable for hosts, such as scripting runtimes, which must recover from misuse.
+    pub fn try_get<T: Component>(&self) -> Result<Option<ArchetypeColumn<'_, T>>, BorrowConflict> {
+        let state = match self.get_state::<T>() {
+            Some(state) => state,
+            None => return Ok(None),
+        };
+        self.debug_assert_initialized(state, 0, self.len);
+        let ptr = self.get_base::<T>(state);
+        if !self.data[state].state.borrow() {
+            return Err(BorrowConflict::new::<T>());
+        }
+        let column = unsafe { slice::from_raw_parts(ptr.as_ptr(), self.len as usize) };
+        Ok(Some(ArchetypeColumn {
+            archetype: self,
+            state,
+            start: 0,
+            column,
+        }))
+    }
+
+    /// Like [`get_mut`](Self::get_mut), but reports a conflicting borrow as an error rather than
+    /// panicking
+    pub fn try_get_mut<T: Component>(
+        &self,
+    ) -> Result<Option<ArchetypeColumnMut<'_, T>>, BorrowConflict> {
+        let state = match self.get_state::<T>() {
+            Some(state) => state,
+            None => return Ok(None),
+        };
+        self.debug_assert_initialized(state, 0, self.len);
         let ptr = self.get_base::<T>(state);
+        if !self.data[state].state.borrow_mut() {
+            return Err(BorrowConflict::new::<T>());
+        }
         let column = unsafe { slice::from_raw_parts_mut(ptr.as_ptr(), self.len as usize) };
+        Ok(Some(ArchetypeColumnMut {
+            archetype: self,
+            column,
+        }))
+    }
+
+    /// Get the `T` components of the entities at indices `start..end`, if present and in bounds
+    ///
+    /// Useful for time-slicing work on large archetypes, e.g. processing a fixed number of
+    /// entities per frame.
+    pub fn column_range<T: Component>(
+        &self,
+        start: u32,
+        end: u32,
+    ) -> Option<ArchetypeColumn<'_, T>> {
+        if start > end || end > self.len {
+            return None;
+        }
+        let state = self.get_state::<T>()?;
+        self.debug_assert_initialized(state, start, end);
+        let ptr = self.get_base::<T>(state);
+        let column = unsafe {
+            slice::from_raw_parts(ptr.as_ptr().add(start as usize), (end - start) as usize)
+        };
+        self.borrow::<T>(state);
+        Some(ArchetypeColumn {
+            archetype: self,
+            state,
+            start,
+            column,
+        })
+    }
+
+    /// Get the `T` components of these entities reinterpreted as `U`, if present
+    ///
+    /// Useful for viewing a column through an equivalent type from another crate without
+    /// converting each element.
+    ///
+    /// # Panics
+    ///
+    /// Panics unless `T` and `U` have the same size and `T`'s alignment is at least `U`'s.
+    ///
+    /// # Safety
+    ///
+    /// Every bit pattern of `T` present in the column must be a valid `U`.
+    pub unsafe fn column_as<T: Component, U: Component>(&self) -> Option<ArchetypeColumn<'_, U>> {
+        assert!(
+            mem::size_of::<T>() == mem::size_of::<U>()
+                && mem::align_of::<T>() >= mem::align_of::<U>(),
+            "{} cannot be reinterpreted as {}",
+            type_name::<T>(),
+            type_name::<U>()
+        );
+        let state = self.get_state::<T>()?;
+        self.debug_assert_initialized(state, 0, self.len);
+        let ptr = self.get_base::<T>(state);
+        let column = slice::from_raw_parts(ptr.as_ptr().cast::<U>(), self.len as usize);
         self.borrow::<T>(state);
         Some(ArchetypeColumn {
+            archetype: self,
+            state,
+            start: 0,
+            column,
+        })
+    }
+
+    /// Get the `T` components of these entities, treating absence as `None` for every entity
+    ///
+    /// Since every entity in an archetype has the same components, this is the storage-level
+    /// equivalent of an `Option<&T>` query.
+    pub fn optional_column<T: Component>(&self) -> OptionalColumn<'_, T> {
+        OptionalColumn {
+            column: self.get::<T>(),
+            len: self.len,
+        }
+    }
+
+    /// Uniquely borrow the `T` components of these entities, if present
+    pub fn get_mut<T: Component>(&self) -> Option<ArchetypeColumnMut<'_, T>> {
+        let state = self.get_state::<T>()?;
+        self.debug_assert_initialized(state, 0, self.len);
+        let ptr = self.get_base::<T>(state);
+        let column = unsafe { slice::from_raw_parts_mut(ptr.as_ptr(), self.len as usize) };
+        self.borrow_mut::<T>(state);
+        Some(ArchetypeColumnMut {
             archetype: self,
             column,
         })
     }
 
+    /// Apply `f` to each `T` component of these entities, if present
+    ///
+    /// # Panics
+    ///
+    /// Panics if the `T` components are already borrowed.
+    pub fn map_column_in_place<T: Component>(&self, f: impl Fn(&mut T)) {
+        if let Some(mut column) = self.get_mut::<T>() {
+            column.iter_mut().for_each(f);
+        }
+    }
+
+    /// Replace each `T` component of these entities with a clone of `value`, if present
+    ///
+    /// The previous components are dropped, so this cannot initialize a column added by
+    /// [`add_column`](Self::add_column); use [`column_uninit`](Self::column_uninit) for that.
+    /// Entities passed to [`mark_dead`](Self::mark_dead) are skipped.
+    pub fn fill_column<T: Component + Clone>(&mut self, value: T) {
+        let state = match self.get_state::<T>() {
+            Some(state) => state,
+            None => return,
+        };
+        let base = self.get_base::<T>(state);
+        let dead = self.sorted_dead();
+        for index in 0..self.len {
+            if dead.binary_search(&index).is_ok() {
+                continue;
+            }
+            self.debug_assert_initialized(state, index, index + 1);
+            unsafe {
+                (*base.as_ptr().add(index as usize)).clone_from(&value);
+            }
+        }
+    }
+
+    /// Indices passed to [`mark_dead`](Self::mark_dead) since the last `compact`, in ascending
+    /// order
+    fn sorted_dead(&self) -> Vec<u32> {
+        let mut dead = self.dead.clone();
+        dead.sort_unstable();
+        dead
+    }
+
+    /// Borrow the `A` components and uniquely borrow the `B` components of these entities, if
+    /// both are present
+    ///
+    /// The storage-level equivalent of a `(&A, &mut B)` query.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `A` and `B` are the same type, since that would alias a column mutably.
+    pub fn split_columns<A: Component, B: Component>(
+        &self,
+    ) -> Option<(ArchetypeColumn<'_, A>, ArchetypeColumnMut<'_, B>)> {
+        assert_distinct_types(&[
+            (TypeId::of::<A>(), type_name::<A>()),
+            (TypeId::of::<B>(), type_name::<B>()),
+        ]);
+        Some((self.get::<A>()?, self.get_mut::<B>()?))
+    }
+
+    /// Uniquely borrow the `A` components of these entities, if present, along with their `B`
+    /// components if those are present too
+    ///
+    /// The storage-level equivalent of an `(&mut A, Option<&B>)` query. The `B` column is only
+    /// borrowed if it exists.
+    ///
+    /// ```
+    /// # use hecs::*;
+    /// let mut world = World::new();
+    /// world.spawn((1, 2u8));
+    /// world.spawn((3,));
+    /// for archetype in world.archetypes() {
+    ///     if let Some(mut columns) = archetype.iter_mut_opt::<i32, u8>() {
+    ///         for (a, b) in columns.iter_mut() {
+    ///             *a += i32::from(b.copied().unwrap_or(10));
+    ///         }
+    ///     }
+    /// }
+    /// let query = world.query_mut::<&i32>();
+    /// let mut values = query.into_iter().map(|(_, &x)| x).collect::<Vec<_>>();
+    /// values.sort_unstable();
+    /// assert_eq!(values, [3, 13]);
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// Panics if `A` and `B` are the same type, since that would alias a column mutably.
+    pub fn iter_mut_opt<A: Component, B: Component>(&self) -> Option<ColumnWithOptional<'_, A, B>> {
+        assert_distinct_types(&[
+            (TypeId::of::<A>(), type_name::<A>()),
+            (TypeId::of::<B>(), type_name::<B>()),
+        ]);
+        Some(ColumnWithOptional {
+            column: self.get_mut::<A>()?,
+            optional: self.optional_column::<B>(),
+        })
+    }
+
+    /// Uniquely borrow the `A` and `B` components of these entities, if both are present
+    ///
+    /// # Panics
+    ///
+    /// Panics if `A` and `B` are the same type, since that would alias a column mutably.
+    pub fn get_mut2<A: Component, B: Component>(
+        &self,
+    ) -> Option<(ArchetypeColumnMut<'_, A>, ArchetypeColumnMut<'_, B>)> {
+        assert_distinct_types(&[
+            (TypeId::of::<A>(), type_name::<A>()),
+            (TypeId::of::<B>(), type_name::<B>()),
+        ]);
+        Some((self.get_mut::<A>()?, self.get_mut::<B>()?))
+    }
+
+    /// Uniquely borrow the `A`, `B`, and `C` components of these entities, if all are present
+    ///
+    /// # Panics
+    ///
+    /// Panics if any two of `A`, `B`, and `C` are the same type.
+    pub fn get_mut3<A: Component, B: Component, C: Component>(
+        &self,
+    ) -> Option<(
+        ArchetypeColumnMut<'_, A>,
+        ArchetypeColumnMut<'_, B>,
+        ArchetypeColumnMut<'_, C>,
+    )> {
+        assert_distinct_types(&[
+            (TypeId::of::<A>(), type_name::<A>()),
+            (TypeId::of::<B>(), type_name::<B>()),
+            (TypeId::of::<C>(), type_name::<C>()),
+        ]);
+        Some((
+            self.get_mut::<A>()?,
+            self.get_mut::<B>()?,
+            self.get_mut::<C>()?,
+        ))
+    }
+
+    /// Borrow the `T` component of the entity at `index`, if present
+    ///
+    /// Returns `None` if `index` is out of bounds or `T` is not stored here.
+    pub fn get_guarded<T: Component>(&self, index: u32) -> Option<Ref<'_, T>> {
+        if index >= self.len {
+            return None;
+        }
+        self.debug_assert_initialized(self.get_state::<T>()?, index, index + 1);
+        unsafe { Ref::new(self, index).ok() }
+    }
+
+    /// Uniquely borrow the `T` component of the entity at `index`, if present
+    ///
+    /// Returns `None` if `index` is out of bounds or `T` is not stored here.
+    pub fn get_guarded_mut<T: Component>(&self, index: u32) -> Option<RefMut<'_, T>> {
+        if index >= self.len {
+            return None;
+        }
+        self.debug_assert_initialized(self.get_state::<T>()?, index, index + 1);
+        unsafe { RefMut::new(self, index).ok() }
+    }
+
+    /// Uniquely borrow the `T` component of the entity at `index`, if present, without dynamic
+    /// borrow checking
+    ///
+    /// Intended for schedulers which have already ruled out conflicting access, as attested by
+    /// `token`.
+    ///
+    /// # Safety
+    ///
+    /// `index` must be in bounds, and no other reference to the component may exist while the
+    /// result is live.
+    #[allow(clippy::mut_from_ref)]
+    pub unsafe fn get_mut_unchecked_tokened<T: Component>(
+        &self,
+        index: u32,
+        token: &AccessToken,
+    ) -> Option<&mut T> {
+        let _ = token;
+        debug_assert!(index < self.len);
+        let state = self.get_state::<T>()?;
+        Some(&mut *self.get_base::<T>(state).as_ptr().add(index as usize))
+    }
+
+    /// Borrow the columns identified by `reads` and uniquely borrow those identified by `writes`
+    ///
+    /// Either every borrow is acquired or none are. Returns `None` if any type is absent or any
+    /// borrow conflicts with one already outstanding, including one made by this call. The borrows
+    /// are released when the returned guard is dropped.
+    pub fn borrow_columns(
+        &self,
+        reads: &[TypeId],
+        writes: &[TypeId],
+    ) -> Option<ArchetypeColumnGuard<'_>> {
+        let mut guard = ArchetypeColumnGuard {
+            archetype: self,
+            borrows: Vec::with_capacity(reads.len() + writes.len()),
+        };
+        let requests = reads.iter().map(|ty| (ty, false));
+        let requests = requests.chain(writes.iter().map(|ty| (ty, true)));
+        for (ty, unique) in requests {
+            // Any borrows acquired so far are released by `guard` on early return
+            let state = *self.index.get(ty)?;
+            let borrow = &self.data[state].state;
+            let acquired = if unique {
+                borrow.borrow_mut()
+            } else {
+                borrow.borrow()
+            };
+            if !acquired {
+                return None;
+            }
+            guard.borrows.push((state, unique));
+        }
+        Some(guard)
+    }
+
     pub(crate) fn borrow<T: Component>(&self, state: usize) {
         assert_eq!(self.types[state].id, TypeId::of::<T>());
 
@@ -150,6 +863,29 @@ impl Archetype {
         self.data[state].state.release_mut();
     }
 
+    fn borrow_dynamic(&self, state: usize) {
+        if !self.data[state].state.borrow() {
+            panic!(
+                "{} already borrowed uniquely",
+                self.types[state].name().unwrap_or("component")
+            );
+        }
+    }
+
+    fn release_dynamic(&self, state: usize) {
+        self.data[state].state.release();
+    }
+
+    /// Bytes of the live components in the column at `state`
+    ///
+    /// The column must be borrowed for the lifetime of the result.
+    unsafe fn column_bytes(&self, state: usize) -> &[u8] {
+        slice::from_raw_parts(
+            self.data[state].storage.as_ptr(),
+            self.len as usize * self.types[state].layout.size(),
+        )
+    }
+
     /// Number of entities in this archetype
     #[inline]
     pub fn len(&self) -> u32 {
@@ -162,6 +898,9 @@ impl Archetype {
         self.len == 0
     }
 
+    /// Address of the first entity ID, for fetches which track their own bounds
+    ///
+    /// Prefer [`ids`](Self::ids) wherever a bounded slice will do.
     #[inline]
     pub(crate) fn entities(&self) -> NonNull<u32> {
         unsafe { NonNull::new_unchecked(self.entities.as_ptr() as *mut _) }
@@ -202,47 +941,420 @@ impl Archetype {
         self.types.iter().map(|typeinfo| typeinfo.id)
     }
 
-    /// `index` must be in-bounds or just past the end
-    pub(crate) unsafe fn get_dynamic(
-        &self,
-        ty: TypeId,
-        size: usize,
-        index: u32,
-    ) -> Option<NonNull<u8>> {
-        debug_assert!(index <= self.len);
-        Some(NonNull::new_unchecked(
-            self.data
-                .get_unchecked(*self.index.get(&ty)?)
-                .storage
-                .as_ptr()
-                .add(size * index as usize)
-                .cast::<u8>(),
-        ))
+    /// Hash of the set of component types stored in this archetype
+    ///
+    /// Archetypes storing the same component types always have the same signature, making it
+    /// suitable for keying caches of per-archetype data. Distinct type sets may rarely collide, so
+    /// caches must confirm a match by comparing [`component_types`](Self::component_types). Like
+    /// `TypeId`s, signatures may differ between builds.
+    pub fn signature(&self) -> u64 {
+        let mut hasher = FxHasher::default();
+        for (id, _) in self.index.0.iter() {
+            id.hash(&mut hasher);
+        }
+        hasher.finish()
     }
 
-    /// Every type must be written immediately after this call
-    pub(crate) unsafe fn allocate(&mut self, id: u32) -> u32 {
+    /// Base address, length, and stride of the column of components with the type identified by
+    /// `ty`, if present
+    ///
+    /// Intended for handing a column to foreign code which iterates `len` elements spaced `stride`
+    /// bytes apart. No borrow is acquired, so the pointer must not be used while the column may be
+    /// mutated, and is invalidated by any change to the archetype's entities.
+    pub fn column_raw(&self, ty: TypeId) -> Option<(*const u8, usize, usize)> {
+        let state = *self.index.get(&ty)?;
+        Some((
+            self.data[state].storage.as_ptr() as *const u8,
+            self.len as usize,
+            self.types[state].layout.size(),
+        ))
+    }
+
+    /// Layout of a struct holding one of each of this archetype's components, in storage order
+    ///
+    /// Useful for converting entities to and from an array-of-structs representation, e.g. at an
+    /// FFI boundary. Because components are stored in order of decreasing alignment, this contains
+    /// no padding except at the end.
+    pub fn entity_layout(&self) -> Layout {
+        self.types
+            .iter()
+            .fold(Layout::new::<()>(), |layout, ty| {
+                layout.extend(ty.layout).unwrap().0
+            })
+            .pad_to_align()
+    }
+
+    /// Type, offset within [`entity_layout`](Self::entity_layout), size, and alignment of each
+    /// component type, in storage order
+    ///
+    /// A single structured description of the archetype's memory for tooling such as debuggers
+    /// and FFI generators. Each type's column is a separate allocation whose elements are spaced by
+    /// its size; offsets describe the packed per-entity representation used by
+    /// [`read_entity_aos`](Self::read_entity_aos).
+    pub fn layout_descriptor(&self) -> Vec<(TypeId, usize, usize, usize)> {
+        self.types
+            .iter()
+            .zip(self.entity_offsets())
+            .map(|(ty, offset)| (ty.id, offset, ty.layout.size(), ty.layout.align()))
+            .collect()
+    }
+
+    /// Offset of each component within [`entity_layout`](Self::entity_layout), in storage order
+    fn entity_offsets(&self) -> impl Iterator<Item = usize> + '_ {
+        let mut layout = Layout::new::<()>();
+        self.types.iter().map(move |ty| {
+            let (extended, offset) = layout.extend(ty.layout).unwrap();
+            layout = extended;
+            offset
+        })
+    }
+
+    /// Copy the components of the entity at `index` into `out`, laid out as
+    /// [`entity_layout`](Self::entity_layout) describes
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index` is out of bounds, `out` is smaller than `entity_layout`, or any of the
+    /// entity's components are uniquely borrowed.
+    ///
+    /// # Safety
+    ///
+    /// The components must not contain padding or other uninitialized bytes.
+    pub unsafe fn read_entity_aos(&self, index: u32, out: &mut [u8]) {
+        assert!(index < self.len, "index out of bounds");
+        assert!(
+            out.len() >= self.entity_layout().size(),
+            "buffer smaller than entity layout"
+        );
+        for (state, offset) in self.entity_offsets().enumerate() {
+            self.debug_assert_initialized(state, index, index + 1);
+            let size = self.types[state].layout.size();
+            self.borrow_dynamic(state);
+            ptr::copy_nonoverlapping(
+                self.data[state].storage.as_ptr().add(index as usize * size),
+                out.as_mut_ptr().add(offset),
+                size,
+            );
+            self.release_dynamic(state);
+        }
+    }
+
+    /// Write the components of the entity at `index` from `src`, laid out as
+    /// [`entity_layout`](Self::entity_layout) describes
+    ///
+    /// The inverse of [`read_entity_aos`](Self::read_entity_aos).
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index` is out of bounds or `src` is smaller than `entity_layout`.
+    ///
+    /// # Safety
+    ///
+    /// `src` must hold valid values of each component type, which are moved into the archetype.
+    /// Any components previously held by the entity are overwritten without being dropped.
+    pub unsafe fn write_entity_aos(&mut self, index: u32, src: &[u8]) {
+        assert!(index < self.len, "index out of bounds");
+        assert!(
+            src.len() >= self.entity_layout().size(),
+            "buffer smaller than entity layout"
+        );
+        let offsets = self.entity_offsets().collect::<Vec<_>>();
+        for (state, offset) in offsets.into_iter().enumerate() {
+            let ty = self.types[state];
+            let size = ty.layout.size();
+            ptr::copy_nonoverlapping(
+                src.as_ptr().add(offset),
+                self.data[state].storage.as_ptr().add(index as usize * size),
+                size,
+            );
+            self.mark_initialized(ty.id, index);
+        }
+    }
+
+    /// Number of distinct alignments among the component types in this archetype
+    pub fn alignment_classes(&self) -> usize {
+        // `types` is sorted by alignment, so equal alignments are adjacent
+        let distinct = self
+            .types
+            .windows(2)
+            .filter(|x| x[0].layout.align() != x[1].layout.align())
+            .count();
+        if self.types.is_empty() {
+            0
+        } else {
+            distinct + 1
+        }
+    }
+
+    /// `index` must be in-bounds or just past the end
+    pub(crate) unsafe fn get_dynamic(
+        &self,
+        ty: TypeId,
+        size: usize,
+        index: u32,
+    ) -> Option<NonNull<u8>> {
+        debug_assert!(index <= self.len);
+        Some(NonNull::new_unchecked(
+            self.data
+                .get_unchecked(*self.index.get(&ty)?)
+                .storage
+                .as_ptr()
+                .add(size * index as usize)
+                .cast::<u8>(),
+        ))
+    }
+
+    /// Every type must be written immediately after this call
+    ///
+    /// Returns the new entity's index and, for a full ring archetype, the ID of the entity evicted
+    /// to make room.
+    pub(crate) unsafe fn allocate(&mut self, id: u32) -> (u32, Option<u32>) {
+        self.debug_assert_thawed();
+        debug_assert!(!self.any_borrowed(), "allocation in a borrowed archetype");
         if self.len as usize == self.entities.len() {
+            if let Some(index) = self.ring_cursor {
+                let evicted = self.entities[index as usize];
+                self.overwrite_slot(id, index);
+                return (index, Some(evicted));
+            }
             self.grow(64);
         }
 
         self.entities[self.len as usize] = id;
+        self.reuse_slots(self.len, self.len + 1);
+        #[cfg(debug_assertions)]
+        self.initialized.push(0);
         self.len += 1;
-        self.len - 1
+        (self.len - 1, None)
+    }
+
+    /// Drop the entity at `index` in ring mode and replace it with a new one with ID `id`
+    fn overwrite_slot(&mut self, id: u32, index: u32) {
+        self.ring_cursor = Some((index + 1) % self.capacity());
+        for (state, (ty, data)) in self.types.iter().zip(&*self.data).enumerate() {
+            if self.is_initialized(state, index) {
+                unsafe {
+                    (ty.drop)(data.storage.as_ptr().add(index as usize * ty.layout.size()));
+                }
+            }
+        }
+        self.entities[index as usize] = id;
+        self.reuse_slots(index, index + 1);
+        #[cfg(debug_assertions)]
+        {
+            self.initialized[index as usize] = 0;
+        }
+    }
+
+    /// Add an entity whose components are exactly those of the bundle type `B`
+    ///
+    /// The storage location of each of `B`'s components is resolved only on the first call for a
+    /// given `B`, so repeatedly spawning the same bundle type is cheaper than a general insertion.
+    /// Returns the new entity's index and the ID of the entity it evicted, if this is a full
+    /// [ring archetype](Self::with_ring_capacity).
+    ///
+    /// # Panics
+    ///
+    /// Panics if `B`'s component types differ from this archetype's.
+    pub fn spawn_static<B: Bundle + 'static>(&mut self, id: u32, bundle: B) -> (u32, Option<u32>) {
+        let key = TypeId::of::<B>();
+        let states = match self.static_layout.take() {
+            Some((cached, states)) if cached == key => Some(states),
+            _ => {
+                assert!(
+                    B::with_static_ids(|ids| *ids == *self.type_ids),
+                    "bundle does not match archetype exactly"
+                );
+                None
+            }
+        };
+        let (index, evicted) = unsafe { self.allocate(id) };
+        let states = match states {
+            Some(states) => {
+                let mut next = states.iter();
+                unsafe {
+                    bundle.put(|ptr, ty| {
+                        self.write_component(*next.next().unwrap(), index, ptr, ty.id());
+                    });
+                }
+                states
+            }
+            None => {
+                let mut states = Vec::new();
+                unsafe {
+                    bundle.put(|ptr, ty| {
+                        let state = *self.index.get(&ty.id()).unwrap();
+                        states.push(state);
+                        self.write_component(state, index, ptr, ty.id());
+                    });
+                }
+                states.into_boxed_slice()
+            }
+        };
+        self.static_layout = Some((key, states));
+        (index, evicted)
+    }
+
+    /// Storage for the `T` components of the `count` entities starting at `start`, for writing
+    ///
+    /// Useful for filling in a column of freshly allocated entities. Existing components are
+    /// overwritten without being dropped.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `T` is not stored here or the entities are out of bounds.
+    ///
+    /// # Safety
+    ///
+    /// Every element must be initialized before the entities are accessed.
+    pub unsafe fn column_uninit<T: Component>(
+        &mut self,
+        start: u32,
+        count: u32,
+    ) -> &mut [MaybeUninit<T>] {
+        assert!(
+            start as usize + count as usize <= self.len as usize,
+            "index out of bounds"
+        );
+        let state = self
+            .get_state::<T>()
+            .expect("component type not stored in archetype");
+        for index in start..start + count {
+            self.mark_initialized(TypeId::of::<T>(), index);
+        }
+        let base = self.get_base::<T>(state).as_ptr().add(start as usize);
+        slice::from_raw_parts_mut(base.cast::<MaybeUninit<T>>(), count as usize)
+    }
+
+    /// Move the component at `src` into column `state` of the entity at `index`
+    unsafe fn write_component(&mut self, state: usize, index: u32, src: *mut u8, ty: TypeId) {
+        let size = self.types[state].layout.size();
+        ptr::copy_nonoverlapping(
+            src,
+            self.data[state].storage.as_ptr().add(index as usize * size),
+            size,
+        );
+        self.mark_initialized(ty, index);
     }
 
     pub(crate) unsafe fn set_len(&mut self, len: u32) {
         debug_assert!(len <= self.capacity());
+        if len > self.len {
+            self.reuse_slots(self.len, len);
+        }
+        #[cfg(debug_assertions)]
+        {
+            let mask = self.all_columns();
+            self.initialized.resize(len as usize, mask);
+        }
+        self.len = len;
+    }
+
+    /// Drop all entities at or past `len`
+    ///
+    /// Useful for rolling back entities allocated since `len` was recorded. Entities passed to
+    /// [`mark_dead`](Self::mark_dead) which are truncated away no longer await `compact`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `len` is greater than the current length.
+    pub fn truncate(&mut self, len: u32) {
+        assert!(
+            len <= self.len,
+            "truncation length exceeds archetype length"
+        );
+        self.debug_assert_thawed();
+        let mut dead = self
+            .dead
+            .iter()
+            .copied()
+            .filter(|&index| index >= len)
+            .collect::<Vec<_>>();
+        dead.sort_unstable();
+        self.dead.retain(|&index| index < len);
+        let live = (len..self.len).filter(|index| dead.binary_search(index).is_err());
+        for state in 0..self.types.len() {
+            for index in live.clone() {
+                self.debug_assert_initialized(state, index, index + 1);
+            }
+        }
+        for (ty, data) in self.types.iter().zip(&*self.data) {
+            for index in live.clone() {
+                unsafe {
+                    let removed = data.storage.as_ptr().add(index as usize * ty.layout.size());
+                    (ty.drop)(removed);
+                }
+            }
+        }
         self.len = len;
+        if len == 0 {
+            self.reset_ring_cursor();
+        }
+        #[cfg(debug_assertions)]
+        self.initialized.truncate(len as usize);
     }
 
     pub(crate) fn reserve(&mut self, additional: u32) {
+        if self.ring_cursor.is_some() {
+            assert!(
+                additional <= self.capacity() - self.len,
+                "ring archetype capacity exceeded"
+            );
+            return;
+        }
         if additional > (self.capacity() - self.len()) {
             let increment = additional - (self.capacity() - self.len());
             self.grow(increment.max(64));
         }
     }
 
+    /// Increase capacity by half, or by 64 entities if that's larger
+    ///
+    /// Insertions double capacity when it runs out, which can waste a lot of memory for very large
+    /// archetypes. Calling this beforehand grows capacity more gradually.
+    pub fn reserve_amortized(&mut self) {
+        self.debug_assert_thawed();
+        self.grow_exact((self.capacity() / 2).max(64));
+    }
+
+    /// Grow capacity to as many entities as fit within `max_bytes` of component data
+    ///
+    /// Never shrinks capacity, and does nothing for archetypes whose components are all
+    /// zero-sized. Entity IDs and other bookkeeping are not counted towards the budget. Returns the
+    /// number of entities which can now be added without reallocating.
+    pub fn reserve_bytes(&mut self, max_bytes: usize) -> u32 {
+        let (size, _) = Self::describe_layout(&self.types);
+        if let Some(cap) = max_bytes.checked_div(size) {
+            let cap = cap.min(u32::MAX as usize);
+            if cap > self.entities.len() {
+                self.debug_assert_thawed();
+                self.set_capacity(cap);
+            }
+        }
+        self.capacity() - self.len
+    }
+
+    /// Number of slots past the end of the `T` column, up to the next multiple of `lanes`, which
+    /// lie within its allocation
+    ///
+    /// Lets SIMD kernels process the final partial chunk of a column with full-width loads. The
+    /// reported slots hold garbage: they must only be read as raw memory, e.g. via
+    /// [`MaybeUninit`] or SIMD load intrinsics, and results computed from them must be discarded.
+    /// Returns 0 if `T` is not stored here.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `lanes` is 0.
+    pub fn reserve_simd_tail<T: Component>(&self, lanes: usize) -> usize {
+        assert!(lanes != 0, "lanes must be nonzero");
+        if !self.has::<T>() {
+            return 0;
+        }
+        let len = self.len as usize;
+        let padded = (len + lanes - 1) / lanes * lanes;
+        (padded - len).min(self.entities.len() - len)
+    }
+
     pub(crate) fn capacity(&self) -> u32 {
         self.entities.len() as u32
     }
@@ -255,9 +1367,278 @@ impl Archetype {
 
     /// Increase capacity by exactly `increment`
     fn grow_exact(&mut self, increment: u32) {
+        #[cfg(feature = "metrics")]
+        self.counters.grows.fetch_add(1, Ordering::Relaxed);
+        self.set_capacity(self.entities.len() + increment as usize);
+    }
+
+    /// Touch every page of unused capacity so the operating system backs it with memory now
+    ///
+    /// Call after reserving capacity, e.g. on a loading screen, to avoid page faults when the
+    /// capacity is later filled.
+    pub fn prefault(&mut self) {
+        // Conservative; larger pages are touched more than once, which is harmless
+        const PAGE_SIZE: usize = 4096;
+        let (len, cap) = (self.len as usize, self.entities.len());
+        for (ty, data) in self.types.iter().zip(&*self.data) {
+            let size = ty.layout.size();
+            let mut offset = len * size;
+            while offset < cap * size {
+                unsafe {
+                    ptr::write_volatile(data.storage.as_ptr().add(offset), 0);
+                }
+                offset += PAGE_SIZE;
+            }
+        }
+    }
+
+    /// Number of times component storage may have moved
+    ///
+    /// Raw column pointers, such as those from [`column_raw`](Self::column_raw), remain valid for
+    /// as long as this is unchanged.
+    pub fn realloc_generation(&self) -> u64 {
+        self.realloc_generation
+    }
+
+    /// Drop this archetype's contents and take over those of `new`, which must store the same
+    /// component types, e.g. after rebuilding its entities during hot-reloading
+    ///
+    /// Unlike assignment, the [`user_data`](Self::user_data) is preserved and the
+    /// [`realloc_generation`](Self::realloc_generation) advances, so this archetype's identity and
+    /// the invalidation of raw pointers into it are both maintained.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `new` stores different component types.
+    pub fn replace_with(&mut self, mut new: Archetype) {
+        assert_eq!(
+            self.type_ids, new.type_ids,
+            "archetypes must have identical component types"
+        );
+        self.debug_assert_thawed();
+        new.user_data = AtomicUsize::new(self.user_data());
+        new.realloc_generation = self.realloc_generation.max(new.realloc_generation) + 1;
+        *self = new;
+    }
+
+    /// Fraction of allocated entity slots which are unused, from 0 to 1
+    ///
+    /// Archetypes with a high value are candidates for [`shrink_to_fit`](Self::shrink_to_fit).
+    pub fn fragmentation(&self) -> f32 {
+        match self.capacity() {
+            0 => 0.0,
+            cap => 1.0 - self.len as f32 / cap as f32,
+        }
+    }
+
+    /// Size of each component type, largest first
+    ///
+    /// Shows which components dominate the per-entity footprint, e.g. when deciding whether to
+    /// split rarely accessed data into separate entities. Types of equal size are in storage order.
+    pub fn column_sizes(&self) -> Vec<(TypeId, usize)> {
+        let mut sizes = self
+            .types
+            .iter()
+            .map(|ty| (ty.id, ty.layout.size()))
+            .collect::<Vec<_>>();
+        sizes.sort_by_key(|&(_, size)| core::cmp::Reverse(size));
+        sizes
+    }
+
+    /// Release unused capacity
+    ///
+    /// An empty archetype releases all of its storage, returning to its initial state.
+    pub fn shrink_to_fit(&mut self) {
+        if self.len != self.capacity() {
+            self.set_capacity(self.len as usize);
+        }
+    }
+
+    /// Add storage for a new component type without moving any entities
+    ///
+    /// Cheaper than migrating every entity to a different archetype when a component is being
+    /// added to all of them at once. The archetype's position in any index keyed by its component
+    /// types is not updated.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the archetype already stores `info`'s type, or in debug builds if this would
+    /// exceed [`MAX_COMPONENTS`](Self::MAX_COMPONENTS).
+    ///
+    /// # Safety
+    ///
+    /// The new component must be written for every existing entity via `put_dynamic` before it is
+    /// accessed or the archetype is dropped.
+    pub unsafe fn add_column(&mut self, info: TypeInfo) {
+        assert!(
+            !self.has_dynamic(info.id),
+            "archetype already stores this component type"
+        );
+        // Checked before the initialization bitmasks are touched, as they have a bit per type
+        debug_assert!(
+            self.types.len() < Self::MAX_COMPONENTS,
+            "archetype has {} component types, exceeding the limit of {}: {:?}",
+            self.types.len() + 1,
+            Self::MAX_COMPONENTS,
+            TypeNames(&self.types)
+        );
+        self.debug_assert_thawed();
+        let cap = self.entities.len();
+        let storage = if info.layout.size() == 0 || cap == 0 {
+            NonNull::new(info.layout.align() as *mut u8).unwrap()
+        } else {
+            NonNull::new(alloc(
+                Layout::from_size_align(info.layout.size() * cap, info.layout.align()).unwrap(),
+            ))
+            .unwrap()
+        };
+        #[cfg(debug_assertions)]
+        let old_ids = self.type_ids.clone();
+        self.types.push(info);
+        let mut data = mem::take(&mut self.data).into_vec();
+        data.push(Data {
+            state: AtomicBorrow::new(),
+            storage,
+        });
+        self.data = data.into_boxed_slice();
+        self.realloc_generation += 1;
+        self.rebuild_index();
+        #[cfg(debug_assertions)]
+        self.remap_initialized(&old_ids);
+    }
+
+    /// Drop and stop storing the component type identified by `ty`, without moving any entities
+    ///
+    /// The in-place counterpart to [`add_column`](Self::add_column). Returns `false` if no such
+    /// component type is stored.
+    pub fn remove_column(&mut self, ty: TypeId) -> bool {
+        let state = match self.index.get(&ty) {
+            Some(&state) => state,
+            None => return false,
+        };
+        self.debug_assert_thawed();
+        #[cfg(debug_assertions)]
+        let old_ids = self.type_ids.clone();
+        let info = self.types.remove(state);
+        let mut data = mem::take(&mut self.data).into_vec();
+        let column = data.remove(state);
+        let dead = self.sorted_dead();
+        for index in 0..self.len {
+            // Unwritten components are leaked rather than dropped, and those of dead entities
+            // were dropped by `mark_dead`
+            if self.is_initialized(state, index) && dead.binary_search(&index).is_err() {
+                unsafe {
+                    (info.drop)(
+                        column
+                            .storage
+                            .as_ptr()
+                            .add(index as usize * info.layout.size()),
+                    );
+                }
+            }
+        }
+        if info.layout.size() != 0 && !self.entities.is_empty() {
+            unsafe {
+                dealloc(
+                    column.storage.as_ptr(),
+                    Layout::from_size_align_unchecked(
+                        info.layout.size() * self.entities.len(),
+                        info.layout.align(),
+                    ),
+                );
+            }
+        }
+        self.data = data.into_boxed_slice();
+        self.realloc_generation += 1;
+        self.rebuild_index();
+        #[cfg(debug_assertions)]
+        self.remap_initialized(&old_ids);
+        true
+    }
+
+    /// Panic in debug builds if the per-type bookkeeping disagrees with `len` or the stored types
+    ///
+    /// An invariant check for fuzzing and tests, aimed at the in-place column operations such as
+    /// [`add_column`](Self::add_column) and [`remove_column`](Self::remove_column). Does nothing in
+    /// release builds.
+    pub fn assert_consistent(&self) {
+        #[cfg(debug_assertions)]
+        {
+            assert_eq!(self.data.len(), self.types.len(), "column count mismatch");
+            assert_eq!(
+                self.type_ids.len(),
+                self.types.len(),
+                "type ID count mismatch"
+            );
+            assert_eq!(self.index.0.len(), self.types.len(), "index size mismatch");
+            for (state, ty) in self.types.iter().enumerate() {
+                assert_eq!(
+                    self.type_ids[state], ty.id,
+                    "{} ID out of place",
+                    ty.type_name
+                );
+                assert_eq!(
+                    self.index.get(&ty.id),
+                    Some(&state),
+                    "{} index entry out of place",
+                    ty.type_name
+                );
+            }
+            assert!(
+                self.len as usize <= self.entities.len(),
+                "length exceeds capacity"
+            );
+            assert_eq!(
+                self.initialized.len(),
+                self.len as usize,
+                "initialization mask count mismatch"
+            );
+            assert!(
+                self.dead.iter().all(|&index| index < self.len),
+                "dead index out of bounds"
+            );
+        }
+    }
+
+    /// Update the initialization bitmasks after the set of component types changed from `old_ids`
+    #[cfg(debug_assertions)]
+    fn remap_initialized(&mut self, old_ids: &[TypeId]) {
+        let new_states = old_ids
+            .iter()
+            .map(|id| self.index.get(id).copied())
+            .collect::<Vec<_>>();
+        for mask in &mut self.initialized {
+            *mask = new_states
+                .iter()
+                .enumerate()
+                .filter(|&(old_state, _)| *mask & (1 << old_state) != 0)
+                .filter_map(|(_, &state)| state)
+                .fold(0, |new, state| new | 1 << state);
+        }
+    }
+
+    /// Reallocate storage to hold exactly `new_cap` entities, which must be at least `len`
+    fn set_capacity(&mut self, new_cap: usize) {
+        if self.ring_cursor.is_some() {
+            // The cursor's slot must remain in bounds, and no more room may be made
+            return;
+        }
         let old_count = self.len as usize;
         let old_cap = self.entities.len();
-        let new_cap = self.entities.len() + increment as usize;
+        debug_assert!(new_cap >= old_count);
+        // Checked before anything is modified so that a panic leaves the archetype intact
+        for info in &self.types {
+            let fits = info
+                .layout
+                .size()
+                .checked_mul(new_cap)
+                .map_or(false, |size| {
+                    Layout::from_size_align(size, info.layout.align()).is_ok()
+                });
+            assert!(fits, "archetype capacity overflow");
+        }
+        // Slots past `len` are never read, so the filler value doesn't restrict the IDs which may
+        // be stored.
         let mut new_entities = vec![!0; new_cap].into_boxed_slice();
         new_entities[0..old_count].copy_from_slice(&self.entities[0..old_count]);
         self.entities = new_entities;
@@ -267,7 +1648,7 @@ impl Archetype {
             .iter()
             .zip(&*self.data)
             .map(|(info, old)| {
-                let storage = if info.layout.size() == 0 {
+                let storage = if info.layout.size() == 0 || new_cap == 0 {
                     NonNull::new(info.layout.align() as *mut u8).unwrap()
                 } else {
                     unsafe {
@@ -283,19 +1664,22 @@ impl Archetype {
                             mem,
                             info.layout.size() * old_count,
                         );
-                        if old_cap > 0 {
-                            dealloc(
-                                old.storage.as_ptr(),
-                                Layout::from_size_align(
-                                    info.layout.size() * old_cap,
-                                    info.layout.align(),
-                                )
-                                .unwrap(),
-                            );
-                        }
+                        self.record_bytes_copied(info.layout.size() * old_count);
                         NonNull::new(mem).unwrap()
                     }
                 };
+                if info.layout.size() != 0 && old_cap > 0 {
+                    unsafe {
+                        dealloc(
+                            old.storage.as_ptr(),
+                            Layout::from_size_align(
+                                info.layout.size() * old_cap,
+                                info.layout.align(),
+                            )
+                            .unwrap(),
+                        );
+                    }
+                }
                 Data {
                     state: AtomicBorrow::new(), // &mut self guarantees no outstanding borrows
                     storage,
@@ -304,83 +1688,691 @@ impl Archetype {
             .collect::<Box<[_]>>();
 
         self.data = new_data;
+        self.realloc_generation += 1;
+    }
+
+    #[inline]
+    fn record_bytes_copied(&self, _bytes: usize) {
+        #[cfg(feature = "metrics")]
+        self.counters
+            .bytes_copied
+            .fetch_add(_bytes, Ordering::Relaxed);
+    }
+
+    /// Counts of storage operations performed on this archetype
+    ///
+    /// Useful for attributing performance problems to excessive growth or component migration.
+    #[cfg(feature = "metrics")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "metrics")))]
+    pub fn metrics(&self) -> ArchetypeMetrics {
+        ArchetypeMetrics {
+            grows: self.counters.grows.load(Ordering::Relaxed),
+            removes: self.counters.removes.load(Ordering::Relaxed),
+            moves: self.counters.moves.load(Ordering::Relaxed),
+            bytes_copied: self.counters.bytes_copied.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Whether removals preserve the relative order of the remaining entities
+    pub fn is_stable_order(&self) -> bool {
+        self.stable_order
     }
 
-    /// Returns the ID of the entity moved into `index`, if any
-    pub(crate) unsafe fn remove(&mut self, index: u32, drop: bool) -> Option<u32> {
+    /// Set whether removals preserve the relative order of the remaining entities
+    ///
+    /// By default, entities are removed by moving the last entity into the vacated slot, which is
+    /// O(1) but reorders storage. In stable order mode, every subsequent entity is instead shifted
+    /// down by one, which is O(n) but keeps iteration order reproducible, as required by e.g.
+    /// deterministic lockstep simulations. Either way, methods which remove entities report the
+    /// range of indices whose entities moved, so callers tracking entity indices can update every
+    /// one of them.
+    pub fn set_stable_order(&mut self, stable: bool) {
+        self.stable_order = stable;
+    }
+
+    /// Returns the range of indices now holding different entities
+    pub(crate) unsafe fn remove(&mut self, index: u32, drop: bool) -> Range<u32> {
+        self.debug_assert_thawed();
+        debug_assert!(!self.any_borrowed(), "removal from a borrowed archetype");
+        #[cfg(feature = "metrics")]
+        self.counters.removes.fetch_add(1, Ordering::Relaxed);
+        if drop {
+            self.debug_assert_entity_initialized(index);
+        }
         let last = self.len - 1;
         for (ty, data) in self.types.iter().zip(&*self.data) {
             let removed = data.storage.as_ptr().add(index as usize * ty.layout.size());
             if drop {
                 (ty.drop)(removed);
             }
-            if index != last {
-                let moved = data.storage.as_ptr().add(last as usize * ty.layout.size());
-                ptr::copy_nonoverlapping(moved, removed, ty.layout.size());
-            }
+            let copied = fill_hole(
+                data.storage.as_ptr(),
+                ty.layout.size(),
+                index as usize,
+                last as usize,
+                self.stable_order,
+            );
+            self.record_bytes_copied(copied);
         }
         self.len = last;
-        if index != last {
-            self.entities[index as usize] = self.entities[last as usize];
-            Some(self.entities[last as usize])
-        } else {
-            None
-        }
+        self.fill_entity_hole(index as usize, last as usize)
     }
 
-    /// Returns the ID of the entity moved into `index`, if any
+    /// Returns the range of indices now holding different entities
     pub(crate) unsafe fn move_to(
         &mut self,
         index: u32,
         mut f: impl FnMut(*mut u8, TypeId, usize),
-    ) -> Option<u32> {
+    ) -> Range<u32> {
+        self.debug_assert_thawed();
+        #[cfg(feature = "metrics")]
+        self.counters.moves.fetch_add(1, Ordering::Relaxed);
+        self.debug_assert_entity_initialized(index);
         let last = self.len - 1;
         for (ty, data) in self.types.iter().zip(&*self.data) {
             let moved_out = data.storage.as_ptr().add(index as usize * ty.layout.size());
             f(moved_out, ty.id(), ty.layout().size());
-            if index != last {
-                let moved = data.storage.as_ptr().add(last as usize * ty.layout.size());
-                ptr::copy_nonoverlapping(moved, moved_out, ty.layout.size());
-            }
+            self.record_bytes_copied(ty.layout.size());
+            let copied = fill_hole(
+                data.storage.as_ptr(),
+                ty.layout.size(),
+                index as usize,
+                last as usize,
+                self.stable_order,
+            );
+            self.record_bytes_copied(copied);
         }
         self.len -= 1;
-        if index != last {
-            self.entities[index as usize] = self.entities[last as usize];
-            Some(self.entities[last as usize])
-        } else {
-            None
+        self.fill_entity_hole(index as usize, last as usize)
+    }
+
+    /// Fill the entity ID slot at `index` after removal, returning the range of indices now holding
+    /// different entities
+    ///
+    /// This is `index..index + 1` normally, or every index from `index` onwards in stable order
+    /// mode, and empty if `index` was the last entity.
+    fn fill_entity_hole(&mut self, index: usize, last: usize) -> Range<u32> {
+        #[cfg(debug_assertions)]
+        {
+            debug_assert_eq!(self.initialized.len(), last + 1);
+            if self.stable_order {
+                self.initialized.remove(index);
+            } else {
+                self.initialized.swap_remove(index);
+            }
+        }
+        unsafe {
+            fill_hole(
+                self.entities.as_mut_ptr().cast(),
+                mem::size_of::<u32>(),
+                index,
+                last,
+                self.stable_order,
+            );
         }
+        let end = if index == last {
+            index
+        } else if self.stable_order {
+            last
+        } else {
+            index + 1
+        };
+        self.reuse_slots(index as u32, end as u32);
+        index as u32..end as u32
     }
 
-    pub(crate) unsafe fn put_dynamic(
-        &mut self,
-        component: *mut u8,
-        ty: TypeId,
-        size: usize,
-        index: u32,
-    ) {
-        let ptr = self
-            .get_dynamic(ty, size, index)
-            .unwrap()
-            .as_ptr()
-            .cast::<u8>();
-        ptr::copy_nonoverlapping(component, ptr, size);
+    /// Record that the slots at `start..end` now hold different entities
+    fn reuse_slots(&mut self, start: u32, end: u32) {
+        let (start, end) = (start as usize, end as usize);
+        let reused = end.min(self.generations.len());
+        for generation in &mut self.generations[start.min(reused)..reused] {
+            *generation = generation.wrapping_add(1);
+        }
+        if end > reused {
+            self.generations.resize(end, 0);
+        }
     }
 
-    /// How, if at all, `Q` will access entities in this archetype
-    pub fn access<Q: Query>(&self) -> Option<Access> {
-        Q::Fetch::access(self)
+    /// Number of times the slot at `index` has been given a different entity
+    ///
+    /// Changes whenever the entity at `index` does, so an `(index, generation)` pair identifies an
+    /// entity for as long as it remains in place.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index` is out of bounds.
+    pub fn generation(&self, index: u32) -> u32 {
+        assert!(index < self.len, "index out of bounds");
+        self.generations[index as usize]
     }
 
-    /// Add components from another archetype with identical components
+    /// Copy the components of the entity at `index` which `target` also stores into the entity at
+    /// `target_index`, leaving this archetype unchanged
+    ///
+    /// Useful for cheaply forking worlds of plain-data components.
+    ///
+    /// # Panics
+    ///
+    /// Panics if either index is out of bounds.
     ///
     /// # Safety
     ///
-    /// Component types must match exactly.
-    pub(crate) unsafe fn merge(&mut self, mut other: Archetype) {
-        self.reserve(other.len);
-        for ((info, dst), src) in self.types.iter().zip(&*self.data).zip(&*other.data) {
-            dst.storage
+    /// Every component type shared by both archetypes must be `Copy`. Existing components at
+    /// `target_index` are overwritten without being dropped.
+    pub unsafe fn copy_to(&self, index: u32, target: &mut Archetype, target_index: u32) {
+        assert!(
+            index < self.len && target_index < target.len,
+            "index out of bounds"
+        );
+        for (state, ty) in self.types.iter().enumerate() {
+            let target_state = match target.index.get(&ty.id) {
+                Some(&x) => x,
+                None => continue,
+            };
+            debug_assert!(!ty.needs_drop, "copied components must be Copy");
+            self.debug_assert_initialized(state, index, index + 1);
+            let size = ty.layout.size();
+            self.borrow_dynamic(state);
+            ptr::copy_nonoverlapping(
+                self.data[state].storage.as_ptr().add(index as usize * size),
+                target.data[target_state]
+                    .storage
+                    .as_ptr()
+                    .add(target_index as usize * size),
+                size,
+            );
+            self.release_dynamic(state);
+            self.record_bytes_copied(size);
+            target.mark_initialized(ty.id, target_index);
+        }
+    }
+
+    /// Clone the components of the entity at `index` whose types are listed in `only` into the
+    /// entity at `target_index`, leaving this archetype unchanged
+    ///
+    /// Useful for instantiating prefabs where some components are reset rather than copied. The
+    /// target entity's other components are left for the caller to write.
+    ///
+    /// # Panics
+    ///
+    /// Panics if either index is out of bounds or a listed type is absent from either archetype.
+    ///
+    /// # Safety
+    ///
+    /// Each [`DynamicClone`] must have been created for the type identified by the `TypeId` it is
+    /// paired with. Existing components at `target_index` are overwritten without being dropped.
+    pub unsafe fn clone_entity_subset(
+        &self,
+        index: u32,
+        target: &mut Archetype,
+        target_index: u32,
+        only: &[(TypeId, DynamicClone)],
+    ) {
+        assert!(
+            index < self.len && target_index < target.len,
+            "index out of bounds"
+        );
+        for &(ty, clone) in only {
+            let state = *self
+                .index
+                .get(&ty)
+                .expect("component type not stored in archetype");
+            assert!(
+                target.has_dynamic(ty),
+                "component type not stored in target archetype"
+            );
+            self.debug_assert_initialized(state, index, index + 1);
+            let size = self.types[state].layout.size();
+            self.borrow_dynamic(state);
+            (clone.func)(
+                self.data[state].storage.as_ptr().add(index as usize * size),
+                &mut |src, info| target.put_dynamic(src, info.id(), size, target_index),
+            );
+            self.release_dynamic(state);
+        }
+    }
+
+    /// Move the entity at `index` into `target`, dropping any components `target` lacks
+    ///
+    /// Returns the entity's index in `target` and the range of indices in `self` now holding
+    /// different entities, as for [`set_stable_order`](Self::set_stable_order).
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index` is out of bounds, `target` stores a component type which this archetype
+    /// lacks, or `target` is a full [ring archetype](Self::with_ring_capacity).
+    pub fn relocate(&mut self, index: u32, target: &mut Archetype) -> (u32, Range<u32>) {
+        assert!(index < self.len, "index out of bounds");
+        assert!(
+            target.type_ids.iter().all(|&ty| self.has_dynamic(ty)),
+            "target archetype has components absent from this archetype"
+        );
+        target.reserve(1);
+        self.debug_assert_entity_initialized(index);
+        unsafe {
+            let (target_index, _) = target.allocate(self.entities[index as usize]);
+            for (ty, data) in self.types.iter().zip(&*self.data) {
+                if !target.has_dynamic(ty.id) {
+                    (ty.drop)(data.storage.as_ptr().add(index as usize * ty.layout.size()));
+                }
+            }
+            let moved = self.move_to(index, |src, ty, size| {
+                if target.has_dynamic(ty) {
+                    target.put_dynamic(src, ty, size, target_index);
+                }
+            });
+            (target_index, moved)
+        }
+    }
+
+    /// Move the entities at `indices` into `target` in a single pass
+    ///
+    /// Components which `target` lacks are dropped. Storage offsets are resolved once per type
+    /// rather than once per entity, making this much cheaper than repeated single-entity moves
+    /// when a structural change applies to many entities at once. Moved entities are appended to
+    /// `target` in descending order of their original index, and entities remaining in `self` may
+    /// be reordered.
+    ///
+    /// # Panics
+    ///
+    /// Panics if any index is out of bounds, or `target` is a [ring
+    /// archetype](Self::with_ring_capacity) without room for every entity.
+    ///
+    /// # Safety
+    ///
+    /// `indices` must be distinct, and every component type of `target` which is absent from
+    /// `self` must be written for the moved entities before they are accessed.
+    pub unsafe fn move_many_to(&mut self, indices: &[u32], target: &mut Archetype) {
+        self.debug_assert_thawed();
+        let mut indices = indices.to_vec();
+        // Removing from the back first ensures no pending index is displaced by a swap-remove
+        indices.sort_unstable_by(|a, b| b.cmp(a));
+        if let Some(&first) = indices.first() {
+            assert!(first < self.len, "index out of bounds");
+        }
+        for &index in &indices {
+            self.debug_assert_entity_initialized(index);
+        }
+        let count = indices.len() as u32;
+        #[cfg(feature = "metrics")]
+        self.counters
+            .moves
+            .fetch_add(indices.len(), Ordering::Relaxed);
+        target.reserve(count);
+        let base = target.len as usize;
+        for (i, &index) in indices.iter().enumerate() {
+            target.entities[base + i] = self.entities[index as usize];
+        }
+        target.reuse_slots(base as u32, base as u32 + count);
+        for (ty, data) in self.types.iter().zip(&*self.data) {
+            let size = ty.layout.size();
+            let dst = target
+                .index
+                .get(&ty.id)
+                .map(|&state| target.data[state].storage.as_ptr().add(base * size));
+            let mut last = self.len as usize;
+            for (i, &index) in indices.iter().enumerate() {
+                last -= 1;
+                let moved_out = data.storage.as_ptr().add(index as usize * size);
+                match dst {
+                    Some(dst) => {
+                        ptr::copy_nonoverlapping(moved_out, dst.add(i * size), size);
+                        self.record_bytes_copied(size);
+                    }
+                    None => (ty.drop)(moved_out),
+                }
+                let copied = fill_hole(
+                    data.storage.as_ptr(),
+                    size,
+                    index as usize,
+                    last,
+                    self.stable_order,
+                );
+                self.record_bytes_copied(copied);
+            }
+        }
+        let mut last = self.len as usize;
+        for &index in &indices {
+            last -= 1;
+            self.fill_entity_hole(index as usize, last);
+        }
+        self.len -= count;
+        target.len += count;
+        #[cfg(debug_assertions)]
+        {
+            let mask = self
+                .types
+                .iter()
+                .filter_map(|ty| target.index.get(&ty.id))
+                .fold(0, |mask, &state| mask | 1 << state);
+            target.initialized.resize(target.len as usize, mask);
+        }
+    }
+
+    /// Move every entity whose index satisfies `pred` into `target`, which must have identical
+    /// component types
+    ///
+    /// Entities are moved as by [`move_many_to`](Self::move_many_to).
+    ///
+    /// # Panics
+    ///
+    /// Panics if the archetypes' component types differ.
+    pub fn partition_out(&mut self, target: &mut Archetype, pred: impl Fn(u32) -> bool) {
+        assert_eq!(
+            self.type_ids, target.type_ids,
+            "archetypes must have identical component types"
+        );
+        let indices = (0..self.len).filter(|&i| pred(i)).collect::<Vec<_>>();
+        unsafe {
+            self.move_many_to(&indices, target);
+        }
+    }
+
+    /// Remove the entity at `index`, moving its components out as a `B`
+    ///
+    /// Components not in `B` are dropped. Returns `None`, leaving the entity in place, if any of
+    /// `B`'s components are absent.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index` is out of bounds or `B` contains a type more than once.
+    pub fn read_bundle<B: Bundle>(&mut self, index: u32) -> Option<B> {
+        assert!(index < self.len, "index out of bounds");
+        self.debug_assert_entity_initialized(index);
+        B::with_static_ids(|ids| {
+            assert!(
+                ids.windows(2).all(|x| x[0] != x[1]),
+                "bundle contains a type more than once"
+            )
+        });
+        let bundle =
+            unsafe { B::get(|ty| self.get_dynamic(ty.id(), ty.layout().size(), index)) }.ok()?;
+        B::with_static_ids(|ids| {
+            for (ty, data) in self.types.iter().zip(&*self.data) {
+                if !ids.contains(&ty.id) {
+                    unsafe {
+                        (ty.drop)(data.storage.as_ptr().add(index as usize * ty.layout.size()));
+                    }
+                }
+            }
+        });
+        unsafe {
+            self.remove(index, false);
+        }
+        Some(bundle)
+    }
+
+    /// Remove every entity whose `T` component satisfies `pred`, moving its components out
+    ///
+    /// Returns the IDs of the removed entities paired with their components, in the order the
+    /// entities were stored. Does nothing if `T` is not stored here.
+    pub fn drain_filter<T: Component>(
+        &mut self,
+        pred: impl Fn(&T) -> bool,
+    ) -> Vec<(u32, EntityBuilder)> {
+        let state = match self.get_state::<T>() {
+            Some(state) => state,
+            None => return Vec::new(),
+        };
+        let mut drained = Vec::new();
+        // Walk backwards so entities swapped into vacated slots have already been visited
+        for index in (0..self.len).rev() {
+            self.debug_assert_initialized(state, index, index + 1);
+            let matched = unsafe { pred(&*self.get_base::<T>(state).as_ptr().add(index as usize)) };
+            if !matched {
+                continue;
+            }
+            self.debug_assert_entity_initialized(index);
+            let mut components = EntityBuilder::new();
+            for (ty, data) in self.types.iter().zip(&*self.data) {
+                unsafe {
+                    components.add_dynamic(
+                        data.storage.as_ptr().add(index as usize * ty.layout.size()),
+                        *ty,
+                    );
+                }
+            }
+            drained.push((self.entities[index as usize], components));
+            unsafe {
+                self.remove(index, false);
+            }
+        }
+        drained.reverse();
+        drained
+    }
+
+    /// Remove the entity at `index`, passing ownership of its `ty` component to `reconstruct` and
+    /// dropping the rest
+    ///
+    /// Lets a dynamic layer hand components to host code without knowing their types at compile
+    /// time. Returns the boxed component and the range of indices now holding different entities,
+    /// as for [`set_stable_order`](Self::set_stable_order).
+    ///
+    /// # Safety
+    ///
+    /// `reconstruct` must take ownership of a value of the type identified by `ty` from the
+    /// pointer it's given, e.g. via `ptr::read`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index` is out of bounds or `ty` is not stored in this archetype.
+    pub unsafe fn read_any(
+        &mut self,
+        ty: TypeId,
+        index: u32,
+        reconstruct: fn(*mut u8) -> Box<dyn Any>,
+    ) -> (Box<dyn Any>, Range<u32>) {
+        assert!(index < self.len, "index out of bounds");
+        let state = *self
+            .index
+            .get(&ty)
+            .expect("component type not stored in archetype");
+        self.debug_assert_entity_initialized(index);
+        let mut value = None;
+        for (i, (info, data)) in self.types.iter().zip(&*self.data).enumerate() {
+            let ptr = data
+                .storage
+                .as_ptr()
+                .add(index as usize * info.layout.size());
+            if i == state {
+                value = Some(reconstruct(ptr));
+            } else {
+                (info.drop)(ptr);
+            }
+        }
+        let moved = self.remove(index, false);
+        (value.unwrap(), moved)
+    }
+
+    /// Drop the components of the entity at `index`, deferring its removal to [`compact`]
+    ///
+    /// Much cheaper than removing many entities individually when combined with a single
+    /// `compact`. The entity's ID remains in [`ids`](Self::ids) until then.
+    ///
+    /// [`compact`]: Self::compact
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index` is out of bounds.
+    ///
+    /// # Safety
+    ///
+    /// The entity must not already be dead. Until `compact` is called, the components of dead
+    /// entities must not be accessed, and no entities may be added, removed, or moved.
+    pub unsafe fn mark_dead(&mut self, index: u32) {
+        assert!(index < self.len, "index out of bounds");
+        self.debug_assert_thawed();
+        self.debug_assert_entity_initialized(index);
+        for (ty, data) in self.types.iter().zip(&*self.data) {
+            (ty.drop)(data.storage.as_ptr().add(index as usize * ty.layout.size()));
+        }
+        self.dead.push(index);
+        #[cfg(debug_assertions)]
+        {
+            self.initialized[index as usize] = 0;
+        }
+    }
+
+    /// Remove every entity passed to [`mark_dead`](Self::mark_dead)
+    pub fn compact(&mut self) {
+        let mut dead = mem::take(&mut self.dead);
+        // Descending order ensures each entity moved into a hole has already been checked
+        dead.sort_unstable_by(|a, b| b.cmp(a));
+        for index in dead {
+            unsafe {
+                self.remove(index, false);
+            }
+        }
+    }
+
+    /// Replace the `T` component of the entity at `index`, returning the previous value
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index` is out of bounds or `T` is not stored here.
+    pub fn replace<T: Component>(&mut self, component: T, index: u32) -> T {
+        assert!(index < self.len, "index out of bounds");
+        let state = self
+            .get_state::<T>()
+            .expect("component type not stored in archetype");
+        self.debug_assert_initialized(state, index, index + 1);
+        unsafe {
+            let slot = self.get_base::<T>(state).as_ptr().add(index as usize);
+            ptr::replace(slot, component)
+        }
+    }
+
+    /// Drop the component with the type identified by `ty` belonging to the entity at `index`
+    ///
+    /// Unlike removal, the entity is left in place. Returns `false` if no such component exists.
+    ///
+    /// # Safety
+    ///
+    /// `index` must be in bounds, and the slot must be rewritten or the entity removed without
+    /// dropping before it is accessed again.
+    pub unsafe fn drop_component(&mut self, ty: TypeId, index: u32) -> bool {
+        debug_assert!(index < self.len);
+        let state = match self.index.get(&ty) {
+            Some(&state) => state,
+            None => return false,
+        };
+        self.debug_assert_initialized(state, index, index + 1);
+        #[cfg(debug_assertions)]
+        {
+            self.initialized[index as usize] &= !(1 << state);
+        }
+        let info = &self.types[state];
+        (info.drop)(
+            self.data[state]
+                .storage
+                .as_ptr()
+                .add(index as usize * info.layout.size()),
+        );
+        true
+    }
+
+    pub(crate) unsafe fn put_dynamic(
+        &mut self,
+        component: *mut u8,
+        ty: TypeId,
+        size: usize,
+        index: u32,
+    ) {
+        let ptr = self
+            .get_dynamic(ty, size, index)
+            .unwrap()
+            .as_ptr()
+            .cast::<u8>();
+        ptr::copy_nonoverlapping(component, ptr, size);
+        self.mark_initialized(ty, index);
+    }
+
+    /// Write the `ty` components of the `count` entities starting at `start` from packed bytes
+    ///
+    /// The storage offset is resolved once, making this much cheaper than repeated `put_dynamic`
+    /// calls when loading a whole column at once.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `ty` is not stored here, the entities are out of bounds, or `src` is not exactly
+    /// `count` components long.
+    ///
+    /// # Safety
+    ///
+    /// `src` must hold `count` valid `ty` components, which are moved into the archetype. Any
+    /// components previously in the destination slots are overwritten without being dropped.
+    pub unsafe fn put_dynamic_many(&mut self, ty: TypeId, start: u32, src: &[u8], count: usize) {
+        let state = *self
+            .index
+            .get(&ty)
+            .expect("component type not stored in archetype");
+        let size = self.types[state].layout.size();
+        assert!(
+            start as usize + count <= self.len as usize,
+            "index out of bounds"
+        );
+        assert_eq!(src.len(), count * size, "source length mismatch");
+        ptr::copy_nonoverlapping(
+            src.as_ptr(),
+            self.data[state].storage.as_ptr().add(start as usize * size),
+            count * size,
+        );
+        self.record_bytes_copied(count * size);
+        for index in start..start + count as u32 {
+            self.mark_initialized(ty, index);
+        }
+    }
+
+    /// Set every byte of every component with the type identified by `ty` to `value`
+    ///
+    /// A fast bulk reset for plain-data columns such as flags and counters. Entities passed to
+    /// [`mark_dead`](Self::mark_dead) are skipped.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `ty` is not stored here.
+    ///
+    /// # Safety
+    ///
+    /// A `ty` consisting entirely of `value` bytes must be valid. Existing components are
+    /// overwritten without being dropped.
+    pub unsafe fn memset_column(&mut self, ty: TypeId, value: u8) {
+        let state = *self
+            .index
+            .get(&ty)
+            .expect("component type not stored in archetype");
+        let size = self.types[state].layout.size();
+        let base = self.data[state].storage.as_ptr();
+        if self.dead.is_empty() {
+            ptr::write_bytes(base, value, self.len as usize * size);
+            for index in 0..self.len {
+                self.mark_initialized(ty, index);
+            }
+            return;
+        }
+        let dead = self.sorted_dead();
+        for index in 0..self.len {
+            if dead.binary_search(&index).is_err() {
+                ptr::write_bytes(base.add(index as usize * size), value, size);
+                self.mark_initialized(ty, index);
+            }
+        }
+    }
+
+    /// How, if at all, `Q` will access entities in this archetype
+    pub fn access<Q: Query>(&self) -> Option<Access> {
+        Q::Fetch::access(self)
+    }
+
+    /// Add components from another archetype with identical components
+    ///
+    /// # Safety
+    ///
+    /// Component types must match exactly.
+    pub(crate) unsafe fn merge(&mut self, mut other: Archetype) {
+        self.reserve(other.len);
+        for ((info, dst), src) in self.types.iter().zip(&*self.data).zip(&*other.data) {
+            dst.storage
                 .as_ptr()
                 .add(self.len as usize * info.layout.size())
                 .copy_from_nonoverlapping(
@@ -388,243 +2380,2119 @@ impl Archetype {
                     other.len as usize * info.layout.size(),
                 )
         }
-        self.len += other.len;
-        other.len = 0;
+        self.reuse_slots(self.len, self.len + other.len);
+        self.len += other.len;
+        other.len = 0;
+        #[cfg(debug_assertions)]
+        self.initialized.append(&mut other.initialized);
+    }
+
+    /// Exchange entities and component storage with another archetype of identical shape
+    ///
+    /// Useful for double-buffering simulation state without reallocating each step. Per-entity
+    /// state such as [`mark_dead`](Self::mark_dead) markers and the next slot to overwrite in ring
+    /// mode moves along with the entities.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the archetypes' component types or capacities differ, or only one of them was
+    /// constructed by [`with_ring_capacity`](Self::with_ring_capacity).
+    pub fn swap_data(&mut self, other: &mut Archetype) {
+        assert_eq!(
+            self.type_ids, other.type_ids,
+            "archetypes must have identical component types"
+        );
+        assert_eq!(
+            self.capacity(),
+            other.capacity(),
+            "archetypes must have identical capacities"
+        );
+        assert_eq!(
+            self.ring_cursor.is_some(),
+            other.ring_cursor.is_some(),
+            "archetypes must both or neither be in ring mode"
+        );
+        mem::swap(&mut self.dead, &mut other.dead);
+        mem::swap(&mut self.ring_cursor, &mut other.ring_cursor);
+        mem::swap(&mut self.data, &mut other.data);
+        self.realloc_generation += 1;
+        other.realloc_generation += 1;
+        mem::swap(&mut self.entities, &mut other.entities);
+        mem::swap(&mut self.generations, &mut other.generations);
+        mem::swap(&mut self.len, &mut other.len);
+        #[cfg(debug_assertions)]
+        mem::swap(&mut self.initialized, &mut other.initialized);
+    }
+
+    /// Whether `other` has the same component types, entity IDs, and component bytes
+    ///
+    /// Compares raw memory rather than invoking `PartialEq`, so this is only meaningful for
+    /// plain-data components without indirection. Intended for asserting that an archetype
+    /// survived e.g. a serialization round-trip intact.
+    ///
+    /// # Safety
+    ///
+    /// The components must not contain padding or other uninitialized bytes.
+    pub unsafe fn data_eq(&self, other: &Archetype) -> bool {
+        if self.type_ids != other.type_ids || self.ids() != other.ids() {
+            return false;
+        }
+        (0..self.types.len()).all(|state| {
+            self.borrow_dynamic(state);
+            other.borrow_dynamic(state);
+            let eq = self.column_bytes(state) == other.column_bytes(state);
+            self.release_dynamic(state);
+            other.release_dynamic(state);
+            eq
+        })
+    }
+
+    /// Raw ID of the entity at `index`, if in bounds
+    ///
+    /// Equivalent to `self.ids().get(index as usize)`.
+    #[inline]
+    pub fn entity_at(&self, index: u32) -> Option<u32> {
+        self.ids().get(index as usize).copied()
+    }
+
+    /// Copy this archetype's entity IDs and raw component data
+    ///
+    /// Component types for which `registry` has no stable ID are omitted. Only meaningful for
+    /// plain-data components; see [`serialize::snapshot`](crate::serialize::snapshot).
+    ///
+    /// # Safety
+    ///
+    /// Components of the types `registry` assigns stable IDs to must not contain padding or other
+    /// uninitialized bytes.
+    #[cfg(feature = "serde")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "serde")))]
+    pub unsafe fn to_snapshot(
+        &self,
+        registry: &(impl SnapshotRegistry + ?Sized),
+    ) -> ArchetypeSnapshot {
+        let mut snapshot = ArchetypeSnapshot {
+            entities: self.ids().to_vec(),
+            ..ArchetypeSnapshot::default()
+        };
+        for (state, ty) in self.types.iter().enumerate() {
+            let id = match registry.stable_id(ty.id) {
+                Some(id) => id,
+                None => continue,
+            };
+            self.debug_assert_initialized(state, 0, self.len);
+            self.borrow_dynamic(state);
+            snapshot.type_ids.push(id);
+            snapshot.columns.push(self.column_bytes(state).to_vec());
+            self.release_dynamic(state);
+        }
+        snapshot
+    }
+
+    /// Reconstruct an archetype from the output of [`to_snapshot`](Self::to_snapshot)
+    ///
+    /// # Safety
+    ///
+    /// Every column must consist of valid values of the type `registry` associates with its ID,
+    /// e.g. because it was produced by `to_snapshot` from components of the same types.
+    #[cfg(feature = "serde")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "serde")))]
+    pub unsafe fn from_snapshot(
+        snapshot: &ArchetypeSnapshot,
+        registry: &(impl SnapshotRegistry + ?Sized),
+    ) -> Result<Self, SnapshotError> {
+        let count = snapshot.entities.len();
+        if snapshot.columns.len() != snapshot.type_ids.len() {
+            return Err(SnapshotError::Malformed);
+        }
+        let mut types = Vec::with_capacity(snapshot.type_ids.len());
+        for (&id, column) in snapshot.type_ids.iter().zip(&snapshot.columns) {
+            let ty = registry
+                .type_info(id)
+                .ok_or(SnapshotError::UnknownType(id))?;
+            if column.len() != ty.layout.size() * count {
+                return Err(SnapshotError::Malformed);
+            }
+            types.push(ty);
+        }
+        let sorted = sort_type_info(types.clone()).map_err(|_| SnapshotError::Malformed)?;
+        let mut archetype = Archetype::new(sorted);
+        archetype.reserve(count as u32);
+        for &id in &snapshot.entities {
+            archetype.allocate(id);
+        }
+        for (ty, column) in types.iter().zip(&snapshot.columns) {
+            archetype.put_dynamic_many(ty.id, 0, column, count);
+        }
+        Ok(archetype)
+    }
+
+    /// Replace this archetype's contents with those of a snapshot, reusing its storage
+    ///
+    /// Cheaper than [`from_snapshot`](Self::from_snapshot) when restoring repeatedly, e.g. for
+    /// rollback, since no allocation occurs if capacity suffices. The current entities are
+    /// dropped. On error, the archetype is left unchanged. A [ring
+    /// archetype](Self::with_ring_capacity) only accepts snapshots which fit within its capacity.
+    ///
+    /// # Safety
+    ///
+    /// Every column must consist of valid values of the type `registry` associates with its ID,
+    /// e.g. because it was produced by `to_snapshot` from components of the same types.
+    #[cfg(feature = "serde")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "serde")))]
+    pub unsafe fn restore_from(
+        &mut self,
+        snapshot: &ArchetypeSnapshot,
+        registry: &(impl SnapshotRegistry + ?Sized),
+    ) -> Result<(), SnapshotError> {
+        let count = snapshot.entities.len();
+        if snapshot.columns.len() != snapshot.type_ids.len() {
+            return Err(SnapshotError::Malformed);
+        }
+        if snapshot.type_ids.len() != self.types.len() {
+            return Err(SnapshotError::TypeMismatch);
+        }
+        if self.ring_cursor.is_some() && count > self.entities.len() {
+            return Err(SnapshotError::CapacityExceeded);
+        }
+        let mut columns = Vec::with_capacity(self.types.len());
+        for ty in &self.types {
+            let column = registry
+                .stable_id(ty.id)
+                .and_then(|id| snapshot.type_ids.iter().position(|&x| x == id))
+                .ok_or(SnapshotError::TypeMismatch)?;
+            let column = &snapshot.columns[column];
+            if column.len() != ty.layout.size() * count {
+                return Err(SnapshotError::Malformed);
+            }
+            columns.push((ty.id, column));
+        }
+        self.debug_assert_thawed();
+        self.clear();
+        self.reserve(count as u32);
+        for &id in &snapshot.entities {
+            self.allocate(id);
+        }
+        for (ty, column) in columns {
+            self.put_dynamic_many(ty, 0, column, count);
+        }
+        Ok(())
+    }
+
+    /// Call `f` with the index and ID of each entity in this archetype
+    pub fn for_each_entity(&self, mut f: impl FnMut(u32, u32)) {
+        for (index, &id) in self.ids().iter().enumerate() {
+            f(index as u32, id);
+        }
+    }
+
+    /// Move out the array of entity IDs, one per slot of capacity, for external management
+    ///
+    /// The first [`len`](Self::len) elements are the IDs of the stored entities. They are replaced
+    /// with placeholders until [`set_entities`](Self::set_entities) is called, while component
+    /// data is unaffected.
+    pub fn take_entities(&mut self) -> Box<[u32]> {
+        let placeholders = vec![!0; self.entities.len()].into_boxed_slice();
+        mem::replace(&mut self.entities, placeholders)
+    }
+
+    /// Replace the array of entity IDs, e.g. with one obtained from
+    /// [`take_entities`](Self::take_entities)
+    ///
+    /// # Panics
+    ///
+    /// Panics if `entities` is not exactly as long as the archetype's capacity.
+    pub fn set_entities(&mut self, entities: Box<[u32]>) {
+        assert_eq!(
+            entities.len(),
+            self.entities.len(),
+            "entity array length must equal capacity"
+        );
+        self.entities = entities;
+    }
+
+    /// Raw IDs of the entities in this archetype
+    ///
+    /// Convertible into [`Entity`](crate::Entity)s with
+    /// [`World::find_entity_from_id()`](crate::World::find_entity_from_id). Useful for efficient
+    /// serialization.
+    #[inline]
+    pub fn ids(&self) -> &[u32] {
+        &self.entities[0..self.len as usize]
+    }
+}
+
+impl Drop for Archetype {
+    fn drop(&mut self) {
+        self.clear();
+        if self.entities.len() == 0 {
+            return;
+        }
+        for (info, data) in self.types.iter().zip(&*self.data) {
+            if info.layout.size() != 0 {
+                unsafe {
+                    dealloc(
+                        data.storage.as_ptr(),
+                        Layout::from_size_align_unchecked(
+                            info.layout.size() * self.entities.len(),
+                            info.layout.align(),
+                        ),
+                    );
+                }
+            }
+        }
+    }
+}
+
+#[cfg(feature = "metrics")]
+#[derive(Default)]
+struct Counters {
+    grows: AtomicUsize,
+    removes: AtomicUsize,
+    moves: AtomicUsize,
+    bytes_copied: AtomicUsize,
+}
+
+/// Proof that component accesses have been checked for conflicts ahead of time
+///
+/// Required by [`Archetype::get_mut_unchecked_tokened`].
+#[derive(Debug)]
+pub struct AccessToken {
+    _opaque: (),
+}
+
+impl AccessToken {
+    /// Mint a token
+    ///
+    /// # Safety
+    ///
+    /// Every access made using the token must be free of conflicts with any other access to the
+    /// same components, e.g. as determined by a scheduler's analysis of its systems.
+    pub unsafe fn new() -> Self {
+        Self { _opaque: () }
+    }
+}
+
+/// Counts of storage operations performed on an [`Archetype`]
+///
+/// Obtained from [`Archetype::metrics`].
+#[cfg(feature = "metrics")]
+#[cfg_attr(docsrs, doc(cfg(feature = "metrics")))]
+#[derive(Debug, Copy, Clone, Default, Eq, PartialEq)]
+pub struct ArchetypeMetrics {
+    /// Number of times storage was reallocated to increase capacity
+    pub grows: usize,
+    /// Number of entities removed
+    pub removes: usize,
+    /// Number of entities moved to another archetype
+    pub moves: usize,
+    /// Bytes of component data copied by reallocation, removal, and moves
+    pub bytes_copied: usize,
+}
+
+/// Fill the hole at `index` of an array of `size`-byte elements whose last live element is at
+/// `last`
+///
+/// If `stable`, every subsequent element is shifted down; otherwise the last element is moved.
+/// Returns the number of bytes copied.
+unsafe fn fill_hole(base: *mut u8, size: usize, index: usize, last: usize, stable: bool) -> usize {
+    if index == last {
+        return 0;
+    }
+    let hole = base.add(index * size);
+    if stable {
+        ptr::copy(hole.add(size), hole, (last - index) * size);
+        (last - index) * size
+    } else {
+        ptr::copy_nonoverlapping(base.add(last * size), hole, size);
+        size
+    }
+}
+
+/// Debug representation of a set of component types by name
+struct TypeNames<'a>(&'a [TypeInfo]);
+
+impl fmt::Debug for TypeNames<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list()
+            .entries(self.0.iter().map(|ty| ty.name().unwrap_or("<unknown>")))
+            .finish()
+    }
+}
+
+/// Panic if any type is requested more than once by a multi-column accessor
+fn assert_distinct_types(types: &[(TypeId, &'static str)]) {
+    for (i, &(id, name)) in types.iter().enumerate() {
+        if types[..i].iter().any(|&(other, _)| other == id) {
+            panic!(
+                "{} requested more than once; a column cannot be uniquely borrowed twice",
+                name
+            );
+        }
+    }
+}
+
+struct Data {
+    state: AtomicBorrow,
+    storage: NonNull<u8>,
+}
+
+/// A hasher optimized for hashing a single TypeId.
+///
+/// TypeId is already thoroughly hashed, so there's no reason to hash it again.
+/// Just leave the bits unchanged.
+#[derive(Default)]
+pub(crate) struct TypeIdHasher {
+    hash: u64,
+}
+
+impl Hasher for TypeIdHasher {
+    fn write_u64(&mut self, n: u64) {
+        // Only a single value can be hashed, so the old hash should be zero.
+        debug_assert_eq!(self.hash, 0);
+        self.hash = n;
+    }
+
+    // Tolerate TypeId being either u64 or u128.
+    fn write_u128(&mut self, n: u128) {
+        debug_assert_eq!(self.hash, 0);
+        self.hash = n as u64;
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        debug_assert_eq!(self.hash, 0);
+
+        // This will only be called if TypeId is neither u64 nor u128, which is not anticipated.
+        // In that case we'll just fall back to using a different hash implementation.
+        let mut hasher = <DefaultHashBuilder as BuildHasher>::Hasher::default();
+        hasher.write(bytes);
+        self.hash = hasher.finish();
+    }
+
+    fn finish(&self) -> u64 {
+        self.hash
+    }
+}
+
+/// A HashMap with TypeId keys
+///
+/// Because TypeId is already a fully-hashed u64 (including data in the high seven bits,
+/// which hashbrown needs), there is no need to hash it again. Instead, this uses the much
+/// faster no-op hash.
+pub(crate) type TypeIdMap<V> = HashMap<TypeId, V, BuildHasherDefault<TypeIdHasher>>;
+
+struct OrderedTypeIdMap<V>(Box<[(TypeId, V)]>);
+
+impl<V> OrderedTypeIdMap<V> {
+    fn new(iter: impl Iterator<Item = (TypeId, V)>) -> Self {
+        let mut vals = iter.collect::<Box<[_]>>();
+        vals.sort_unstable_by_key(|(id, _)| *id);
+        Self(vals)
+    }
+
+    fn search(&self, id: &TypeId) -> Option<usize> {
+        // Most archetypes have only a handful of components, for which a linear scan beats the
+        // unpredictable branches of a binary search
+        if self.0.len() <= 8 {
+            return self.0.iter().position(|(x, _)| x == id);
+        }
+        self.0.binary_search_by_key(id, |(id, _)| *id).ok()
+    }
+
+    fn contains_key(&self, id: &TypeId) -> bool {
+        self.search(id).is_some()
+    }
+
+    fn get(&self, id: &TypeId) -> Option<&V> {
+        self.search(id).map(move |idx| &self.0[idx].1)
+    }
+}
+
+/// Metadata required to store a component.
+///
+/// All told, this means a [`TypeId`], to be able to dynamically name/check the component type; a
+/// [`Layout`], so that we know how to allocate memory for this component type; and a drop function
+/// which internally calls [`core::ptr::drop_in_place`] with the correct type parameter.
+#[derive(Debug, Copy, Clone)]
+pub struct TypeInfo {
+    id: TypeId,
+    layout: Layout,
+    drop: unsafe fn(*mut u8),
+    needs_drop: bool,
+    stable_hash: StableHash,
+    #[cfg(debug_assertions)]
+    type_name: &'static str,
+}
+
+/// Source of a [`TypeInfo::stable_hash`], computed on demand because only serialization needs it
+#[derive(Debug, Copy, Clone)]
+enum StableHash {
+    /// Hash of the type's name
+    Name(fn() -> &'static str),
+    Explicit(u64),
+}
+
+impl TypeInfo {
+    /// Construct a `TypeInfo` directly from the static type.
+    pub fn of<T: 'static>() -> Self {
+        unsafe fn drop_ptr<T: 'static>(x: *mut u8) {
+            #[cfg(feature = "drop-trace")]
+            crate::drop_trace::record(TypeId::of::<T>());
+            x.cast::<T>().drop_in_place()
+        }
+
+        Self {
+            id: TypeId::of::<T>(),
+            layout: Layout::new::<T>(),
+            drop: drop_ptr::<T>,
+            needs_drop: mem::needs_drop::<T>(),
+            stable_hash: StableHash::Name(core::any::type_name::<T>),
+            #[cfg(debug_assertions)]
+            type_name: core::any::type_name::<T>(),
+        }
+    }
+
+    /// Construct a `TypeInfo` from its components. This is useful in the rare case that you have
+    /// some kind of pointer to raw bytes/erased memory holding a component type, coming from a
+    /// source unrelated to hecs, and you want to treat it as an insertable component by
+    /// implementing the `DynamicBundle` API.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `layout`'s alignment is not a nonzero power of two, which is only possible if it
+    /// was constructed unsoundly. Checking here reports the problem at registration time rather
+    /// than deep inside archetype allocation.
+    pub fn from_parts(id: TypeId, layout: Layout, drop: unsafe fn(*mut u8)) -> Self {
+        assert!(
+            layout.align().is_power_of_two(),
+            "component layout has invalid alignment {}",
+            layout.align()
+        );
+        Self {
+            id,
+            layout,
+            drop,
+            needs_drop: true,
+            stable_hash: StableHash::Explicit(0),
+            #[cfg(debug_assertions)]
+            type_name: "<unknown> (TypeInfo constructed from parts)",
+        }
+    }
+
+    /// Replace the value returned by [`stable_hash`](Self::stable_hash)
+    ///
+    /// `TypeInfo`s constructed with [`from_parts`](Self::from_parts) have a stable hash of 0, which
+    /// marks them as unregistered for serialization, unless one is supplied here.
+    pub fn with_stable_hash(mut self, stable_hash: u64) -> Self {
+        self.stable_hash = StableHash::Explicit(stable_hash);
+        self
+    }
+
+    /// Access the `TypeId` for this component type.
+    pub fn id(&self) -> TypeId {
+        self.id
+    }
+
+    /// Name of the component type, if known
+    ///
+    /// Only tracked in debug builds.
+    pub(crate) fn name(&self) -> Option<&'static str> {
+        #[cfg(debug_assertions)]
+        return Some(self.type_name);
+        #[cfg(not(debug_assertions))]
+        None
+    }
+
+    /// Hash of the component type's name, which unlike its `TypeId` is consistent across builds
+    ///
+    /// Suitable for identifying component types in serialized data. Distinct types have a small
+    /// chance of colliding, as do types whose names are changed by moving them between modules or
+    /// by compiler upgrades; applications that need guarantees should assign explicit IDs instead,
+    /// e.g. with a custom `SnapshotRegistry` when using the `serde` feature.
+    pub fn stable_hash(&self) -> u64 {
+        match self.stable_hash {
+            StableHash::Name(name) => fx_hash(name().as_bytes()),
+            StableHash::Explicit(hash) => hash,
+        }
+    }
+
+    /// Hash of the component type's size and alignment, which is consistent across builds
+    ///
+    /// Saving this alongside raw component bytes allows a loader to detect when a type's layout
+    /// has changed, making the bytes unsafe to restore. Changes which preserve size and alignment,
+    /// such as reordering fields, are not detected.
+    pub fn layout_signature(&self) -> u64 {
+        let mut bytes = [0; 16];
+        bytes[..8].copy_from_slice(&(self.layout.size() as u64).to_le_bytes());
+        bytes[8..].copy_from_slice(&(self.layout.align() as u64).to_le_bytes());
+        fx_hash(&bytes)
+    }
+
+    /// Access the `Layout` of this component type.
+    pub fn layout(&self) -> Layout {
+        self.layout
+    }
+
+    /// Directly call the destructor on a pointer to data of this component type.
+    ///
+    /// # Safety
+    ///
+    /// All of the caveats of [`core::ptr::drop_in_place`] apply, with the additional requirement
+    /// that this method is being called on a pointer to an object of the correct component type.
+    pub unsafe fn drop(&self, data: *mut u8) {
+        (self.drop)(data)
+    }
+
+    /// Whether the destructor might do anything
+    ///
+    /// Always `true` for `TypeInfo`s constructed with [`from_parts`](Self::from_parts).
+    pub fn needs_drop(&self) -> bool {
+        self.needs_drop
+    }
+
+    /// Get the function pointer encoding the destructor for the component type this `TypeInfo`
+    /// represents.
+    pub fn drop_shim(&self) -> unsafe fn(*mut u8) {
+        self.drop
+    }
+}
+
+fn fx_hash(bytes: &[u8]) -> u64 {
+    let mut hasher = FxHasher::default();
+    hasher.write(bytes);
+    hasher.finish()
+}
+
+/// The FxHash algorithm used by rustc, which is fast and has no dependency on process state
+#[derive(Default)]
+struct FxHasher(u64);
+
+impl FxHasher {
+    fn add(&mut self, word: u64) {
+        const SEED: u64 = 0x51_7c_c1_b7_27_22_0a_95;
+        self.0 = (self.0.rotate_left(5) ^ word).wrapping_mul(SEED);
+    }
+}
+
+impl Hasher for FxHasher {
+    fn write(&mut self, bytes: &[u8]) {
+        let mut chunks = bytes.chunks_exact(8);
+        for chunk in &mut chunks {
+            self.add(u64::from_le_bytes(
+                core::convert::TryInto::try_into(chunk).unwrap(),
+            ));
+        }
+        for &byte in chunks.remainder() {
+            self.add(u64::from(byte));
+        }
+    }
+
+    fn write_u64(&mut self, n: u64) {
+        self.add(n);
+    }
+
+    fn finish(&self) -> u64 {
+        self.0
+    }
+}
+
+impl PartialOrd for TypeInfo {
+    fn partial_cmp(&self, other: &Self) -> Option<core::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for TypeInfo {
+    /// Order by alignment, descending. Ties broken with TypeId.
+    fn cmp(&self, other: &Self) -> core::cmp::Ordering {
+        self.layout
+            .align()
+            .cmp(&other.layout.align())
+            .reverse()
+            .then_with(|| self.id.cmp(&other.id))
+    }
+}
+
+impl PartialEq for TypeInfo {
+    fn eq(&self, other: &Self) -> bool {
+        self.id == other.id
+    }
+}
+
+impl Eq for TypeInfo {}
+
+/// Error indicating that a column could not be borrowed due to an outstanding conflicting borrow
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct BorrowConflict(&'static str);
+
+impl BorrowConflict {
+    /// Construct an error representing a conflicting borrow of `T`
+    pub fn new<T: Component>() -> Self {
+        Self(type_name::<T>())
+    }
+}
+
+impl fmt::Display for BorrowConflict {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} already borrowed", self.0)
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for BorrowConflict {}
+
+/// Shared reference to a single column of component data in an [`Archetype`]
+///
+/// The borrow prevents the world from being modified until the `ArchetypeColumn` is dropped. To
+/// retain components for longer, e.g. for processing on a background thread over several frames,
+/// copy them out with [`Archetype::snapshot_column`].
+pub struct ArchetypeColumn<'a, T: Component> {
+    archetype: &'a Archetype,
+    /// Borrowed column, which may store a type other than `T` with identical layout
+    state: usize,
+    /// Index of the first entity covered by `column`
+    start: u32,
+    column: &'a [T],
+}
+
+impl<'a, T: Component> ArchetypeColumn<'a, T> {
+    /// Iterate over the components paired with the raw IDs of the entities they belong to
+    ///
+    /// See [`Archetype::ids`] for details on entity IDs.
+    pub fn iter_with_id(&self) -> impl ExactSizeIterator<Item = (u32, &T)> + '_ {
+        self.archetype.ids()[self.start as usize..]
+            .iter()
+            .copied()
+            .zip(self.column.iter())
+    }
+
+    /// Iterate over the components paired with the raw IDs and indices of the entities they belong
+    /// to
+    ///
+    /// Useful for building secondary indices, which may refer back to entities by either.
+    pub fn enumerate(&self) -> impl ExactSizeIterator<Item = (u32, u32, &T)> + '_ {
+        let start = self.start;
+        self.iter_with_id()
+            .enumerate()
+            .map(move |(i, (id, x))| (id, start + i as u32, x))
+    }
+
+    /// Iterate over the components with their entities' indices, from last to first
+    ///
+    /// This is the safe order for removing entities as they're visited: a swap-remove only moves
+    /// entities from later indices, which have already been visited, so every index yet to be
+    /// visited stays valid. As removal requires this column to be released first, collect the
+    /// indices to remove and then remove them in the order they were yielded.
+    pub fn iter_rev(&self) -> impl ExactSizeIterator<Item = (u32, &T)> + '_ {
+        let start = self.start;
+        self.column
+            .iter()
+            .enumerate()
+            .rev()
+            .map(move |(i, x)| (start + i as u32, x))
+    }
+
+    /// Iterate over the components satisfying `pred`, with their entities' indices
+    pub fn iter_filtered<F>(&self, pred: F) -> impl Iterator<Item = (u32, &T)> + '_
+    where
+        F: Fn(&T) -> bool + 'a,
+    {
+        let start = self.start;
+        self.column
+            .iter()
+            .enumerate()
+            .filter(move |(_, x)| pred(x))
+            .map(move |(i, x)| (start + i as u32, x))
+    }
+
+    /// Iterate over every unordered pair of distinct components, with their entities' indices
+    ///
+    /// Yields `n * (n - 1) / 2` pairs for a column of length `n`, so this is only suitable for
+    /// small columns, e.g. brute-force broad-phase collision detection.
+    pub fn pairs(&self) -> impl Iterator<Item = ((u32, &T), (u32, &T))> + '_ {
+        let start = self.start;
+        self.column.iter().enumerate().flat_map(move |(i, x)| {
+            self.column[i + 1..]
+                .iter()
+                .enumerate()
+                .map(move |(j, y)| ((start + i as u32, x), (start + (i + 1 + j) as u32, y)))
+        })
+    }
+}
+
+impl<'a, T, const N: usize> ArchetypeColumn<'a, [T; N]>
+where
+    [T; N]: Component,
+{
+    /// View the column as a row-major matrix with one row per entity
+    ///
+    /// Returns the flattened elements, the number of rows, and the number of columns `N`. Useful
+    /// for batched numeric work over e.g. vector-valued components.
+    pub fn as_2d(&self) -> (&[T], usize, usize) {
+        let rows = self.column.len();
+        let flat = unsafe { slice::from_raw_parts(self.column.as_ptr().cast::<T>(), rows * N) };
+        (flat, rows, N)
+    }
+}
+
+impl<T: Component> Deref for ArchetypeColumn<'_, T> {
+    type Target = [T];
+    fn deref(&self) -> &[T] {
+        self.column
+    }
+}
+
+impl<T: Component> Drop for ArchetypeColumn<'_, T> {
+    fn drop(&mut self) {
+        self.archetype.release_dynamic(self.state);
+    }
+}
+
+impl<T: Component> Clone for ArchetypeColumn<'_, T> {
+    fn clone(&self) -> Self {
+        self.archetype.borrow_dynamic(self.state);
+        Self {
+            archetype: self.archetype,
+            state: self.state,
+            start: self.start,
+            column: self.column,
+        }
+    }
+}
+
+impl<T: Component + fmt::Debug> fmt::Debug for ArchetypeColumn<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.column.fmt(f)
+    }
+}
+
+/// Shared reference to a column of component data in an [`Archetype`] which might not exist
+///
+/// Obtained from [`Archetype::optional_column`].
+pub struct OptionalColumn<'a, T: Component> {
+    column: Option<ArchetypeColumn<'a, T>>,
+    len: u32,
+}
+
+impl<T: Component> OptionalColumn<'_, T> {
+    /// Iterate over each entity's component, or `None` for every entity if the column is absent
+    pub fn iter(&self) -> impl ExactSizeIterator<Item = Option<&T>> + '_ {
+        (0..self.len as usize).map(move |i| self.column.as_ref().map(|column| &column[i]))
+    }
+}
+
+impl<T: Component + fmt::Debug> fmt::Debug for OptionalColumn<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+/// Unique reference to a column of component data in an [`Archetype`], alongside a shared
+/// reference to another column which might not exist
+///
+/// Obtained from [`Archetype::iter_mut_opt`].
+pub struct ColumnWithOptional<'a, A: Component, B: Component> {
+    column: ArchetypeColumnMut<'a, A>,
+    optional: OptionalColumn<'a, B>,
+}
+
+impl<A: Component, B: Component> ColumnWithOptional<'_, A, B> {
+    /// Iterate over each entity's `A` component, paired with its `B` component if present
+    pub fn iter_mut(&mut self) -> impl ExactSizeIterator<Item = (&mut A, Option<&B>)> + '_ {
+        self.column.iter_mut().zip(self.optional.iter())
+    }
+}
+
+/// Borrows of a set of columns in an [`Archetype`], released on drop
+///
+/// Obtained from [`Archetype::borrow_columns`].
+pub struct ArchetypeColumnGuard<'a> {
+    archetype: &'a Archetype,
+    /// State index of each borrowed column, and whether the borrow is unique
+    borrows: Vec<(usize, bool)>,
+}
+
+impl ArchetypeColumnGuard<'_> {
+    /// Iterate over the bytes of each component with the type identified by `ty`, with their
+    /// entities' indices
+    ///
+    /// Allows type-erased code such as custom serializers to process components individually.
+    /// Returns `None` if `ty` is not among the types borrowed by this guard.
+    ///
+    /// # Safety
+    ///
+    /// The components must not contain padding or other uninitialized bytes.
+    pub unsafe fn elements(
+        &self,
+        ty: TypeId,
+    ) -> Option<impl ExactSizeIterator<Item = (u32, &[u8])> + '_> {
+        let state = *self.archetype.index.get(&ty)?;
+        if !self.borrows.iter().any(|&(x, _)| x == state) {
+            return None;
+        }
+        self.archetype
+            .debug_assert_initialized(state, 0, self.archetype.len);
+        let size = self.archetype.types[state].layout.size();
+        // The borrow is held by `self`
+        let bytes = self.archetype.column_bytes(state);
+        Some((0..self.archetype.len).map(move |index| {
+            let start = index as usize * size;
+            (index, &bytes[start..start + size])
+        }))
+    }
+}
+
+impl Drop for ArchetypeColumnGuard<'_> {
+    fn drop(&mut self) {
+        for &(state, unique) in &self.borrows {
+            let borrow = &self.archetype.data[state].state;
+            if unique {
+                borrow.release_mut();
+            } else {
+                borrow.release();
+            }
+        }
+    }
+}
+
+/// Exclusive lock on an [`Archetype`], released on drop
+///
+/// Obtained from [`Archetype::try_lock`].
+pub struct ArchetypeGuard<'a> {
+    archetype: &'a Archetype,
+}
+
+impl Deref for ArchetypeGuard<'_> {
+    type Target = Archetype;
+    fn deref(&self) -> &Archetype {
+        self.archetype
+    }
+}
+
+impl Drop for ArchetypeGuard<'_> {
+    fn drop(&mut self) {
+        self.archetype.lock.release_mut();
+    }
+}
+
+/// Unique reference to a single column of component data in an [`Archetype`]
+pub struct ArchetypeColumnMut<'a, T: Component> {
+    archetype: &'a Archetype,
+    column: &'a mut [T],
+}
+
+impl<T: Component> ArchetypeColumnMut<'_, T> {
+    /// Iterate over the components paired with the raw IDs of the entities they belong to
+    ///
+    /// See [`Archetype::ids`] for details on entity IDs.
+    pub fn iter_mut_with_id(&mut self) -> impl ExactSizeIterator<Item = (u32, &mut T)> + '_ {
+        self.archetype
+            .ids()
+            .iter()
+            .copied()
+            .zip(self.column.iter_mut())
+    }
+}
+
+impl<T: Component> Deref for ArchetypeColumnMut<'_, T> {
+    type Target = [T];
+    fn deref(&self) -> &[T] {
+        self.column
+    }
+}
+
+impl<T: Component> DerefMut for ArchetypeColumnMut<'_, T> {
+    fn deref_mut(&mut self) -> &mut [T] {
+        self.column
+    }
+}
+
+impl<T: Component> Drop for ArchetypeColumnMut<'_, T> {
+    fn drop(&mut self) {
+        let state = self.archetype.get_state::<T>().unwrap();
+        self.archetype.release_mut::<T>(state);
+    }
+}
+
+impl<T: Component + fmt::Debug> fmt::Debug for ArchetypeColumnMut<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.column.fmt(f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::alloc::sync::Arc;
+    use crate::DynamicBundle;
+
+    fn archetype_of<B: Bundle>() -> Archetype {
+        B::with_static_type_info(|info| Archetype::new(info.to_vec()))
+    }
+
+    fn push(archetype: &mut Archetype, id: u32, components: impl DynamicBundle) -> u32 {
+        unsafe {
+            let (index, _) = archetype.allocate(id);
+            components.put(|ptr, ty| {
+                archetype.put_dynamic(ptr, ty.id(), ty.layout().size(), index);
+            });
+            index
+        }
+    }
+
+    #[test]
+    fn swap_data() {
+        let mut a = archetype_of::<(u32, bool)>();
+        let mut b = archetype_of::<(u32, bool)>();
+        push(&mut a, 0, (1u32, true));
+        push(&mut a, 1, (2u32, false));
+        push(&mut b, 2, (3u32, true));
+        a.swap_data(&mut b);
+        assert_eq!(a.ids(), [2]);
+        assert_eq!(*a.get::<u32>().unwrap(), [3]);
+        assert_eq!(b.ids(), [0, 1]);
+        assert_eq!(*b.get::<bool>().unwrap(), [true, false]);
+    }
+
+    #[test]
+    fn swap_data_dead() {
+        let rc = Arc::new(());
+        let mut a = archetype_of::<(Arc<()>,)>();
+        let mut b = archetype_of::<(Arc<()>,)>();
+        push(&mut a, 0, (rc.clone(),));
+        push(&mut a, 1, (rc.clone(),));
+        push(&mut b, 2, (rc.clone(),));
+        unsafe {
+            a.mark_dead(0);
+        }
+        a.swap_data(&mut b);
+        a.compact();
+        assert_eq!(a.ids(), [2]);
+        b.compact();
+        assert_eq!(b.ids(), [1]);
+        assert_eq!(Arc::strong_count(&rc), 3);
+    }
+
+    #[test]
+    fn has_exact_types() {
+        let a = archetype_of::<(u32, bool)>();
+        assert!(a.has_exact_types(&[TypeId::of::<bool>(), TypeId::of::<u32>()]));
+        assert!(!a.has_exact_types(&[TypeId::of::<u32>()]));
+        assert!(!a.has_exact_types(&[TypeId::of::<u32>(), TypeId::of::<i32>()]));
+    }
+
+    #[test]
+    fn alignment_classes() {
+        assert_eq!(archetype_of::<()>().alignment_classes(), 0);
+        assert_eq!(archetype_of::<(u32, i32, u8)>().alignment_classes(), 2);
+    }
+
+    #[test]
+    fn missing_types() {
+        let a = archetype_of::<(u32, bool)>();
+        let requested = [TypeId::of::<i8>(), TypeId::of::<u32>(), TypeId::of::<u8>()];
+        assert_eq!(
+            a.missing_types(&requested),
+            [TypeId::of::<i8>(), TypeId::of::<u8>()]
+        );
+    }
+
+    #[test]
+    fn type_diff() {
+        let a = archetype_of::<(u32, bool, u64)>();
+        let b = archetype_of::<(u8, u64, u16)>();
+        let (only_a, only_b) = a.type_diff(&b);
+        assert_eq!(only_a, [TypeId::of::<u32>(), TypeId::of::<bool>()]);
+        assert_eq!(only_b, [TypeId::of::<u16>(), TypeId::of::<u8>()]);
+        assert_eq!(a.type_diff(&a), (Vec::new(), Vec::new()));
+    }
+
+    #[test]
+    fn type_info() {
+        let a = archetype_of::<(u16, [u8; 3])>();
+        let info = a.type_info(TypeId::of::<[u8; 3]>()).unwrap();
+        assert_eq!(info.layout(), Layout::new::<[u8; 3]>());
+        assert!(a.type_info(TypeId::of::<u8>()).is_none());
+    }
+
+    #[test]
+    fn entity_layout() {
+        let a = archetype_of::<(u8, u64, [u16; 3])>();
+        let layout = a.entity_layout();
+        assert_eq!((layout.size(), layout.align()), (16, 8));
+        let a = archetype_of::<()>();
+        assert_eq!(a.entity_layout(), Layout::new::<()>());
+    }
+
+    #[test]
+    fn describe_layout() {
+        let layout = <(u8, u64, [u16; 3])>::with_static_type_info(Archetype::describe_layout);
+        assert_eq!(layout, (15, 8));
+        assert_eq!(Archetype::describe_layout(&[]), (0, 1));
+    }
+
+    #[test]
+    fn clear_fast() {
+        let mut a = archetype_of::<(u32, ())>();
+        push(&mut a, 0, (1u32, ()));
+        a.clear_fast();
+        assert!(a.is_empty());
+        push(&mut a, 1, (2u32, ()));
+        assert_eq!(*a.get::<u32>().unwrap(), [2]);
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "clear_fast would leak")]
+    fn clear_fast_leak() {
+        let mut a = archetype_of::<(Arc<()>,)>();
+        push(&mut a, 0, (Arc::new(()),));
+        a.clear_fast();
+    }
+
+    #[test]
+    fn add_column() {
+        let mut a = archetype_of::<(u8,)>();
+        push(&mut a, 0, (1u8,));
+        push(&mut a, 1, (2u8,));
+        unsafe {
+            a.add_column(TypeInfo::of::<Arc<u64>>());
+            for (i, value) in [10u64, 20].iter().enumerate() {
+                let mut component = Arc::new(*value);
+                a.put_dynamic(
+                    (&mut component as *mut Arc<u64>).cast(),
+                    TypeId::of::<Arc<u64>>(),
+                    mem::size_of::<Arc<u64>>(),
+                    i as u32,
+                );
+                mem::forget(component);
+            }
+        }
+        assert!(a.has::<Arc<u64>>());
+        assert_eq!(*a.get::<u8>().unwrap(), [1, 2]);
+        let column = a.get::<Arc<u64>>().unwrap();
+        assert_eq!(column.iter().map(|x| **x).collect::<Vec<_>>(), [10, 20]);
+        drop(column);
+        push(&mut a, 2, (3u8, Arc::new(30u64)));
+        assert_eq!(*a.get::<Arc<u64>>().unwrap()[2], 30);
+    }
+
+    #[test]
+    #[should_panic(expected = "already stores")]
+    fn add_column_duplicate() {
+        let mut a = archetype_of::<(u8,)>();
+        unsafe {
+            a.add_column(TypeInfo::of::<u8>());
+        }
+    }
+
+    #[test]
+    fn max_id() {
+        let mut a = archetype_of::<(u8,)>();
+        push(&mut a, u32::MAX, (1u8,));
+        push(&mut a, 0, (2u8,));
+        assert_eq!(a.ids(), [u32::MAX, 0]);
+        assert_eq!(unsafe { a.remove(0, false) }, 0..1);
+        assert_eq!(unsafe { a.remove(0, false) }, 0..0);
+        push(&mut a, u32::MAX, (3u8,));
+        assert_eq!(a.ids(), [u32::MAX]);
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    fn uninitialized() {
+        use std::panic::{catch_unwind, AssertUnwindSafe};
+
+        let mut a = archetype_of::<(u32, Arc<()>)>();
+        push(&mut a, 0, (1u32, Arc::new(())));
+        let (index, _) = unsafe { a.allocate(1) };
+        let mut x = 2u32;
+        unsafe {
+            a.put_dynamic((&mut x as *mut u32).cast(), TypeId::of::<u32>(), 4, index);
+        }
+        assert_eq!(*a.get::<u32>().unwrap(), [1, 2]);
+        let err = catch_unwind(AssertUnwindSafe(|| a.get::<Arc<()>>().map(|_| ()))).unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<std::string::String>().unwrap(),
+            "alloc::sync::Arc<()> component of entity at index 1 was never written"
+        );
+        assert!(catch_unwind(AssertUnwindSafe(|| unsafe { a.remove(1, true) })).is_err());
+        assert!(a.get_guarded::<Arc<()>>(0).is_some());
+        // Dropping the archetype must not touch the unwritten component
+    }
+
+    #[test]
+    fn put_dynamic_many() {
+        let mut a = archetype_of::<(u16, u8)>();
+        unsafe {
+            for id in 0..3 {
+                let (index, _) = a.allocate(id);
+                let mut x = 0u8;
+                a.put_dynamic((&mut x as *mut u8).cast(), TypeId::of::<u8>(), 1, index);
+            }
+            let src = [1u16, 2, 3];
+            let bytes = slice::from_raw_parts(src.as_ptr().cast::<u8>(), 6);
+            a.put_dynamic_many(TypeId::of::<u16>(), 0, bytes, 3);
+        }
+        assert_eq!(*a.get::<u16>().unwrap(), [1, 2, 3]);
+    }
+
+    #[test]
+    fn generation() {
+        let mut a = archetype_of::<(u8,)>();
+        push(&mut a, 0, (0u8,));
+        push(&mut a, 1, (1u8,));
+        push(&mut a, 2, (2u8,));
+        assert_eq!(a.generation(0), 0);
+        unsafe {
+            a.remove(0, true);
+        }
+        // Entity 2 moved into slot 0
+        assert_eq!(a.generation(0), 1);
+        assert_eq!(a.generation(1), 0);
+        push(&mut a, 3, (3u8,));
+        assert_eq!(a.generation(2), 1);
+        a.clear();
+        a.shrink_to_fit();
+        push(&mut a, 4, (4u8,));
+        assert_eq!(a.generation(0), 2);
+    }
+
+    #[test]
+    fn fragmentation() {
+        let mut a = archetype_of::<(u8,)>();
+        assert_eq!(a.fragmentation(), 0.0);
+        for i in 0..16 {
+            push(&mut a, i, (0u8,));
+        }
+        assert_eq!(a.capacity(), 64);
+        assert_eq!(a.fragmentation(), 0.75);
+        a.shrink_to_fit();
+        assert_eq!(a.fragmentation(), 0.0);
+    }
+
+    #[test]
+    fn copy_to() {
+        let mut a = archetype_of::<(u32, u8)>();
+        push(&mut a, 0, (1u32, 2u8));
+        push(&mut a, 1, (3u32, 4u8));
+        let mut b = archetype_of::<(u32, u16)>();
+        push(&mut b, 7, (0u32, 5u16));
+        unsafe {
+            a.copy_to(1, &mut b, 0);
+        }
+        assert_eq!(*a.get::<u32>().unwrap(), [1, 3]);
+        assert_eq!(*b.get::<u32>().unwrap(), [3]);
+        assert_eq!(*b.get::<u16>().unwrap(), [5]);
+        assert_eq!(b.ids(), [7]);
+    }
+
+    #[test]
+    fn replace() {
+        let mut a = archetype_of::<(Arc<u32>, u8)>();
+        let old = Arc::new(1u32);
+        push(&mut a, 0, (old.clone(), 2u8));
+        let returned = a.replace(Arc::new(3u32), 0);
+        assert!(Arc::ptr_eq(&returned, &old));
+        assert_eq!(*a.get::<Arc<u32>>().unwrap()[0], 3);
+        drop(returned);
+        assert_eq!(Arc::strong_count(&old), 1);
+    }
+
+    #[test]
+    fn spawn_static() {
+        let mut a = archetype_of::<(u8, u32, u16)>();
+        for i in 0..3 {
+            a.spawn_static(i, (i as u8, i * 10, i as u16 * 100));
+        }
+        a.spawn_static(3, (40u32, 4u8, 400u16));
+        a.spawn_static(4, (5u8, 50u32, 500u16));
+        assert_eq!(a.ids(), [0, 1, 2, 3, 4]);
+        assert_eq!(*a.get::<u8>().unwrap(), [0, 1, 2, 4, 5]);
+        assert_eq!(*a.get::<u32>().unwrap(), [0, 10, 20, 40, 50]);
+        assert_eq!(*a.get::<u16>().unwrap(), [0, 100, 200, 400, 500]);
+    }
+
+    #[test]
+    #[should_panic(expected = "does not match")]
+    fn spawn_static_mismatch() {
+        let mut a = archetype_of::<(u8, u32)>();
+        a.spawn_static(0, (1u8,));
+    }
+
+    #[test]
+    fn column_uninit() {
+        let mut a = archetype_of::<(u32, u8)>();
+        unsafe {
+            for id in 0..4 {
+                a.allocate(id);
+            }
+            for (i, x) in a.column_uninit::<u32>(0, 4).iter_mut().enumerate() {
+                x.as_mut_ptr().write(i as u32 * 2);
+            }
+            for x in a.column_uninit::<u8>(0, 4) {
+                x.as_mut_ptr().write(7);
+            }
+            a.column_uninit::<u32>(1, 2)[1].as_mut_ptr().write(9);
+        }
+        assert_eq!(*a.get::<u32>().unwrap(), [0, 2, 9, 6]);
+        assert_eq!(*a.get::<u8>().unwrap(), [7; 4]);
+    }
+
+    #[test]
+    fn partition_out() {
+        let mut a = archetype_of::<(u32, Arc<()>)>();
+        let mut b = archetype_of::<(u32, Arc<()>)>();
+        let rc = Arc::new(());
+        for i in 0..5 {
+            push(&mut a, i, (i, rc.clone()));
+        }
+        a.partition_out(&mut b, |i| i % 2 == 0);
+        let mut remaining = a.get::<u32>().unwrap().to_vec();
+        remaining.sort_unstable();
+        assert_eq!(remaining, [1, 3]);
+        let mut moved = b.get::<u32>().unwrap().to_vec();
+        moved.sort_unstable();
+        assert_eq!(moved, [0, 2, 4]);
+        assert_eq!(a.ids(), &*a.get::<u32>().unwrap());
+        assert_eq!(b.ids(), &*b.get::<u32>().unwrap());
+        assert_eq!(Arc::strong_count(&rc), 6);
+    }
+
+    #[test]
+    #[should_panic(expected = "identical component types")]
+    fn partition_out_mismatched() {
+        let mut a = archetype_of::<(u32,)>();
+        let mut b = archetype_of::<(u8,)>();
+        a.partition_out(&mut b, |_| true);
+    }
+
+    #[test]
+    fn prefault() {
+        let mut a = archetype_of::<(u32, [u8; 4096], ())>();
+        push(&mut a, 0, (1u32, [2u8; 4096], ()));
+        a.reserve(100);
+        a.prefault();
+        assert_eq!(*a.get::<u32>().unwrap(), [1]);
+        assert_eq!(a.get::<[u8; 4096]>().unwrap()[0], [2; 4096]);
+    }
+
+    #[test]
+    fn realloc_generation() {
+        let mut a = archetype_of::<(u32,)>();
+        let initial = a.realloc_generation();
+        push(&mut a, 0, (0u32,));
+        let generation = a.realloc_generation();
+        assert_ne!(generation, initial);
+        let ptr = a.column_raw(TypeId::of::<u32>()).unwrap().0;
+        for i in 1..64 {
+            push(&mut a, i, (i,));
+        }
+        assert_eq!(a.realloc_generation(), generation);
+        assert_eq!(a.column_raw(TypeId::of::<u32>()).unwrap().0, ptr);
+        push(&mut a, 64, (64u32,));
+        assert_ne!(a.realloc_generation(), generation);
+    }
+
+    #[test]
+    fn remove_column() {
+        let rc = Arc::new(());
+        let mut a = archetype_of::<(u8, Arc<()>, u64)>();
+        push(&mut a, 0, (1u8, rc.clone(), 2u64));
+        push(&mut a, 1, (3u8, rc.clone(), 4u64));
+        assert!(a.remove_column(TypeId::of::<Arc<()>>()));
+        assert_eq!(Arc::strong_count(&rc), 1);
+        assert!(!a.has::<Arc<()>>());
+        assert!(!a.remove_column(TypeId::of::<Arc<()>>()));
+        assert_eq!(*a.get::<u8>().unwrap(), [1, 3]);
+        assert_eq!(*a.get::<u64>().unwrap(), [2, 4]);
+        assert!(a.remove_column(TypeId::of::<u64>()));
+        push(&mut a, 2, (5u8,));
+        assert_eq!(*a.get::<u8>().unwrap(), [1, 3, 5]);
+    }
+
+    #[test]
+    fn stable_hash() {
+        assert_eq!(
+            TypeInfo::of::<u32>().stable_hash(),
+            TypeInfo::of::<u32>().stable_hash()
+        );
+        assert_ne!(
+            TypeInfo::of::<u32>().stable_hash(),
+            TypeInfo::of::<u64>().stable_hash()
+        );
+        assert_eq!(fx_hash(b""), 0);
+        assert_eq!(
+            TypeInfo::of::<u32>().layout_signature(),
+            TypeInfo::of::<f32>().layout_signature()
+        );
+        assert_ne!(
+            TypeInfo::of::<u32>().layout_signature(),
+            TypeInfo::of::<[u16; 2]>().layout_signature()
+        );
+        let info = TypeInfo::from_parts(TypeId::of::<u8>(), Layout::new::<u8>(), |_| {});
+        assert_eq!(info.with_stable_hash(7).stable_hash(), 7);
+    }
+
+    #[test]
+    fn fill_column() {
+        let rc = Arc::new(());
+        let mut a = archetype_of::<(u32, Arc<()>)>();
+        push(&mut a, 0, (1u32, Arc::new(())));
+        push(&mut a, 1, (2u32, Arc::new(())));
+        a.fill_column(rc.clone());
+        a.fill_column(7u32);
+        a.fill_column(0u8);
+        assert_eq!(Arc::strong_count(&rc), 3);
+        assert_eq!(*a.get::<u32>().unwrap(), [7, 7]);
+
+        push(&mut a, 2, (3u32, Arc::new(())));
+        unsafe {
+            a.mark_dead(1);
+            a.memset_column(TypeId::of::<u32>(), 0);
+        }
+        a.fill_column(rc.clone());
+        assert_eq!(Arc::strong_count(&rc), 3);
+        a.compact();
+        assert_eq!(*a.get::<u32>().unwrap(), [0, 0]);
+    }
+
+    #[test]
+    fn map_column_in_place() {
+        let mut a = archetype_of::<(u32, u8)>();
+        push(&mut a, 0, (1u32, 2u8));
+        push(&mut a, 1, (3u32, 4u8));
+        a.map_column_in_place(|x: &mut u32| *x *= 10);
+        a.map_column_in_place(|_: &mut u16| unreachable!());
+        assert_eq!(*a.get::<u32>().unwrap(), [10, 30]);
+        assert_eq!(*a.get::<u8>().unwrap(), [2, 4]);
+    }
+
+    #[test]
+    fn reserve_amortized() {
+        let mut a = archetype_of::<(u32,)>();
+        a.reserve_amortized();
+        assert_eq!(a.capacity(), 64);
+        a.set_capacity(200);
+        a.reserve_amortized();
+        assert_eq!(a.capacity(), 300);
+    }
+
+    #[test]
+    fn any_borrowed() {
+        let mut a = archetype_of::<(u32, u8)>();
+        push(&mut a, 0, (1u32, 2u8));
+        assert!(!a.any_borrowed());
+        let column = a.get::<u8>().unwrap();
+        assert!(a.any_borrowed());
+        drop(column);
+        let column = a.get_mut::<u32>().unwrap();
+        assert!(a.any_borrowed());
+        drop(column);
+        assert!(!a.any_borrowed());
+    }
+
+    #[test]
+    fn drain_filter() {
+        let rc = Arc::new(());
+        let mut a = archetype_of::<(u32, Arc<()>)>();
+        for i in 0..6u32 {
+            push(&mut a, i + 10, (i, rc.clone()));
+        }
+        let drained = a.drain_filter(|&x: &u32| x % 2 == 0);
+        assert_eq!(
+            drained.iter().map(|(id, _)| *id).collect::<Vec<_>>(),
+            [10, 12, 14]
+        );
+        assert_eq!(*drained[1].1.get::<u32>().unwrap(), 2);
+        assert_eq!(Arc::strong_count(&rc), 7);
+        drop(drained);
+        assert_eq!(Arc::strong_count(&rc), 4);
+        let mut rest = a.get::<u32>().unwrap().to_vec();
+        rest.sort_unstable();
+        assert_eq!(rest, [1, 3, 5]);
+        assert!(a.drain_filter(|_: &u8| true).is_empty());
+    }
+
+    #[test]
+    fn try_lock() {
+        let mut a = archetype_of::<(u32,)>();
+        push(&mut a, 0, (1u32,));
+        let guard = a.try_lock().unwrap();
+        assert!(a.try_lock().is_none());
+        assert_eq!(*guard.get::<u32>().unwrap(), [1]);
+        drop(guard);
+        assert!(a.try_lock().is_some());
+    }
+
+    #[test]
+    fn reserve_simd_tail() {
+        let mut a = archetype_of::<(u32,)>();
+        assert_eq!(a.reserve_simd_tail::<u32>(8), 0);
+        for i in 0..5u32 {
+            push(&mut a, i, (i,));
+        }
+        assert_eq!(a.reserve_simd_tail::<u32>(8), 3);
+        assert_eq!(a.reserve_simd_tail::<u32>(5), 0);
+        assert_eq!(a.reserve_simd_tail::<u8>(8), 0);
+        a.set_capacity(6);
+        assert_eq!(a.reserve_simd_tail::<u32>(8), 1);
+    }
+
+    #[test]
+    fn ordered_type_id_map() {
+        let ids = [
+            TypeId::of::<u8>(),
+            TypeId::of::<u16>(),
+            TypeId::of::<u32>(),
+            TypeId::of::<u64>(),
+            TypeId::of::<u128>(),
+            TypeId::of::<i8>(),
+            TypeId::of::<i16>(),
+            TypeId::of::<i32>(),
+            TypeId::of::<i64>(),
+            TypeId::of::<i128>(),
+        ];
+        for n in [3, ids.len()] {
+            let map = OrderedTypeIdMap::new(ids[..n].iter().copied().zip(0..));
+            for (i, id) in ids.iter().enumerate() {
+                assert_eq!(map.get(id).copied(), Some(i).filter(|&i| i < n));
+            }
+        }
+    }
+
+    #[test]
+    fn for_each_entity() {
+        let mut a = archetype_of::<(u32,)>();
+        push(&mut a, 5, (1u32,));
+        push(&mut a, 3, (2u32,));
+        let mut entities = Vec::new();
+        a.for_each_entity(|index, id| entities.push((index, id)));
+        assert_eq!(entities, [(0, 5), (1, 3)]);
+    }
+
+    #[test]
+    fn take_entities() {
+        let mut a = archetype_of::<(u32,)>();
+        push(&mut a, 5, (1u32,));
+        push(&mut a, 6, (2u32,));
+        let mut entities = a.take_entities();
+        assert_eq!(entities.len(), a.capacity() as usize);
+        assert_eq!(entities[..2], [5, 6]);
+        assert_ne!(a.ids(), [5, 6]);
+        entities[0] = 7;
+        a.set_entities(entities);
+        assert_eq!(a.ids(), [7, 6]);
+        assert_eq!(*a.get::<u32>().unwrap(), [1, 2]);
+    }
+
+    #[test]
+    #[should_panic(expected = "entity array length must equal capacity")]
+    fn set_entities_wrong_length() {
+        let mut a = archetype_of::<(u32,)>();
+        push(&mut a, 5, (1u32,));
+        a.set_entities(Box::new([5]));
+    }
+
+    #[test]
+    fn histogram() {
+        let mut a = archetype_of::<(u8, u32)>();
+        for (i, x) in [3u8, 1, 3, 3, 2, 1].iter().enumerate() {
+            push(&mut a, i as u32, (*x, 0u32));
+        }
+        let counts = a.histogram::<u8>();
+        assert_eq!(counts.len(), 3);
+        assert_eq!((counts[&3], counts[&1], counts[&2]), (3, 2, 1));
+        assert_eq!(a.histogram::<u32>().get(&0), Some(&6));
+        assert!(a.histogram::<u16>().is_empty());
     }
 
-    /// Raw IDs of the entities in this archetype
-    ///
-    /// Convertible into [`Entity`](crate::Entity)s with
-    /// [`World::find_entity_from_id()`](crate::World::find_entity_from_id). Useful for efficient
-    /// serialization.
-    #[inline]
-    pub fn ids(&self) -> &[u32] {
-        &self.entities[0..self.len as usize]
+    #[test]
+    fn large_component() {
+        #[repr(align(64))]
+        #[derive(Copy, Clone)]
+        struct Page([u8; 4096]);
+
+        let mut a = archetype_of::<(Page,)>();
+        for i in 0..1000u32 {
+            push(&mut a, i, (Page([i as u8; 4096]),));
+        }
+        let (ptr, len, stride) = a.column_raw(TypeId::of::<Page>()).unwrap();
+        assert_eq!(ptr as usize % 64, 0);
+        assert_eq!((len, stride), (1000, 4096));
+        let column = a.get::<Page>().unwrap();
+        assert!(column
+            .iter()
+            .enumerate()
+            .all(|(i, page)| page.0.iter().all(|&x| x == i as u8)));
     }
-}
 
-impl Drop for Archetype {
-    fn drop(&mut self) {
-        self.clear();
-        if self.entities.len() == 0 {
-            return;
+    #[test]
+    #[cfg(target_pointer_width = "64")]
+    #[should_panic(expected = "archetype capacity overflow")]
+    fn capacity_overflow() {
+        let layout = Layout::from_size_align(1 << 62, 1).unwrap();
+        let mut a = Archetype::new(vec![TypeInfo::from_parts(
+            TypeId::of::<u8>(),
+            layout,
+            |_| {},
+        )]);
+        a.reserve(1);
+    }
+
+    #[test]
+    fn mark_dead() {
+        let rc = Arc::new(());
+        let mut a = archetype_of::<(u32, Arc<()>)>();
+        for i in 0..6u32 {
+            push(&mut a, i, (i, rc.clone()));
         }
-        for (info, data) in self.types.iter().zip(&*self.data) {
-            if info.layout.size() != 0 {
-                unsafe {
-                    dealloc(
-                        data.storage.as_ptr(),
-                        Layout::from_size_align_unchecked(
-                            info.layout.size() * self.entities.len(),
-                            info.layout.align(),
-                        ),
-                    );
-                }
-            }
+        unsafe {
+            a.mark_dead(1);
+            a.mark_dead(5);
+            a.mark_dead(4);
+        }
+        assert_eq!(Arc::strong_count(&rc), 4);
+        assert_eq!(a.ids(), [0, 1, 2, 3, 4, 5]);
+        a.compact();
+        assert_eq!(Arc::strong_count(&rc), 4);
+        assert_eq!(a.len(), 3);
+        let mut ids = a.ids().to_vec();
+        ids.sort_unstable();
+        assert_eq!(ids, [0, 2, 3]);
+        assert!(a
+            .get::<u32>()
+            .unwrap()
+            .iter()
+            .zip(a.ids())
+            .all(|(x, id)| x == id));
+        unsafe {
+            a.mark_dead(0);
         }
+        drop(a);
+        assert_eq!(Arc::strong_count(&rc), 1);
     }
-}
 
-struct Data {
-    state: AtomicBorrow,
-    storage: NonNull<u8>,
-}
+    #[test]
+    fn reserve_bytes() {
+        let mut a = archetype_of::<(u32, u16)>();
+        push(&mut a, 0, (1u32, 2u16));
+        assert_eq!(a.reserve_bytes(600), 99);
+        assert_eq!(a.capacity(), 100);
+        assert_eq!(a.reserve_bytes(60), 99);
+        assert_eq!(a.capacity(), 100);
+        let mut a = archetype_of::<((),)>();
+        assert_eq!(a.reserve_bytes(600), 0);
+    }
 
-/// A hasher optimized for hashing a single TypeId.
-///
-/// TypeId is already thoroughly hashed, so there's no reason to hash it again.
-/// Just leave the bits unchanged.
-#[derive(Default)]
-pub(crate) struct TypeIdHasher {
-    hash: u64,
-}
+    #[test]
+    fn clone_entity_subset() {
+        use crate::alloc::string::String;
 
-impl Hasher for TypeIdHasher {
-    fn write_u64(&mut self, n: u64) {
-        // Only a single value can be hashed, so the old hash should be zero.
-        debug_assert_eq!(self.hash, 0);
-        self.hash = n;
+        let mut a = archetype_of::<(String, u32, u8)>();
+        push(&mut a, 0, (String::from("abc"), 1u32, 2u8));
+        let mut b = archetype_of::<(String, u32)>();
+        unsafe {
+            let (index, _) = b.allocate(1);
+            let only = [(TypeId::of::<String>(), DynamicClone::new::<String>())];
+            a.clone_entity_subset(0, &mut b, index, &only);
+            b.put_dynamic(
+                (&mut 5u32 as *mut u32).cast(),
+                TypeId::of::<u32>(),
+                4,
+                index,
+            );
+        }
+        assert_eq!(*a.get::<String>().unwrap(), ["abc"]);
+        assert_eq!(*b.get::<String>().unwrap(), ["abc"]);
+        assert_eq!(*b.get::<u32>().unwrap(), [5]);
     }
 
-    // Tolerate TypeId being either u64 or u128.
-    fn write_u128(&mut self, n: u128) {
-        debug_assert_eq!(self.hash, 0);
-        self.hash = n as u64;
+    #[test]
+    fn ring_capacity() {
+        let rc = Arc::new(());
+        let mut a = Archetype::with_ring_capacity(
+            vec![TypeInfo::of::<Arc<()>>(), TypeInfo::of::<u32>()],
+            3,
+        );
+        for i in 0..5u32 {
+            push(&mut a, i, (i, rc.clone()));
+        }
+        assert_eq!(Arc::strong_count(&rc), 4);
+        assert_eq!(a.capacity(), 3);
+        assert_eq!(a.ids(), [3, 4, 2]);
+        assert_eq!(*a.get::<u32>().unwrap(), [3, 4, 2]);
+        assert_eq!(a.generation(0), 1);
+        assert_eq!(a.spawn_static(5, (5u32, rc.clone())), (2, Some(2)));
+        assert_eq!(Arc::strong_count(&rc), 4);
+        a.reserve_amortized();
+        a.reserve_bytes(usize::MAX);
+        unsafe {
+            a.remove(0, true);
+        }
+        a.shrink_to_fit();
+        assert_eq!(a.capacity(), 3);
+        a.reserve(1);
+        assert_eq!(a.spawn_static(6, (6u32, rc.clone())), (2, None));
     }
 
-    fn write(&mut self, bytes: &[u8]) {
-        debug_assert_eq!(self.hash, 0);
+    #[test]
+    fn ring_capacity_clear() {
+        let mut a = Archetype::with_ring_capacity(vec![TypeInfo::of::<u32>()], 3);
+        for i in 0..4u32 {
+            push(&mut a, i, (i,));
+        }
+        a.clear();
+        for i in 10..13u32 {
+            push(&mut a, i, (i,));
+        }
+        assert_eq!(a.spawn_static(13, (13u32,)), (0, Some(10)));
+        a.clear_fast();
+        for i in 20..23u32 {
+            push(&mut a, i, (i,));
+        }
+        assert_eq!(a.spawn_static(23, (23u32,)), (0, Some(20)));
+    }
 
-        // This will only be called if TypeId is neither u64 nor u128, which is not anticipated.
-        // In that case we'll just fall back to using a different hash implementation.
-        let mut hasher = <DefaultHashBuilder as BuildHasher>::Hasher::default();
-        hasher.write(bytes);
-        self.hash = hasher.finish();
+    #[test]
+    #[should_panic(expected = "ring archetype capacity exceeded")]
+    fn ring_capacity_reserve() {
+        let mut a = Archetype::with_ring_capacity(vec![TypeInfo::of::<u32>()], 2);
+        push(&mut a, 0, (0u32,));
+        a.reserve(2);
     }
 
-    fn finish(&self) -> u64 {
-        self.hash
+    #[test]
+    fn signature() {
+        let a = archetype_of::<(u32, bool)>();
+        let b = Archetype::new(vec![TypeInfo::of::<u32>(), TypeInfo::of::<bool>()]);
+        let c = archetype_of::<(u32,)>();
+        assert_eq!(a.signature(), b.signature());
+        assert_ne!(a.signature(), c.signature());
     }
-}
 
-/// A HashMap with TypeId keys
-///
-/// Because TypeId is already a fully-hashed u64 (including data in the high seven bits,
-/// which hashbrown needs), there is no need to hash it again. Instead, this uses the much
-/// faster no-op hash.
-pub(crate) type TypeIdMap<V> = HashMap<TypeId, V, BuildHasherDefault<TypeIdHasher>>;
+    #[test]
+    fn column_sizes() {
+        let a = archetype_of::<(u8, [u8; 16], u64)>();
+        assert_eq!(
+            a.column_sizes(),
+            [
+                (TypeId::of::<[u8; 16]>(), 16),
+                (TypeId::of::<u64>(), 8),
+                (TypeId::of::<u8>(), 1),
+            ]
+        );
+    }
 
-struct OrderedTypeIdMap<V>(Box<[(TypeId, V)]>);
+    #[test]
+    fn layout_descriptor() {
+        let a = archetype_of::<(u8, u32, [u16; 3])>();
+        assert_eq!(
+            a.layout_descriptor(),
+            [
+                (TypeId::of::<u32>(), 0, 4, 4),
+                (TypeId::of::<[u16; 3]>(), 4, 6, 2),
+                (TypeId::of::<u8>(), 10, 1, 1),
+            ]
+        );
+    }
 
-impl<V> OrderedTypeIdMap<V> {
-    fn new(iter: impl Iterator<Item = (TypeId, V)>) -> Self {
-        let mut vals = iter.collect::<Box<[_]>>();
-        vals.sort_unstable_by_key(|(id, _)| *id);
-        Self(vals)
+    #[test]
+    fn entity_aos() {
+        let mut a = archetype_of::<(u8, u32, u16)>();
+        push(&mut a, 0, (1u8, 2u32, 3u16));
+        push(&mut a, 1, (0u8, 0u32, 0u16));
+        let mut buf = [0xff; 8];
+        unsafe {
+            a.read_entity_aos(0, &mut buf);
+        }
+        let mut expected = [0xff; 8];
+        expected[..4].copy_from_slice(&2u32.to_ne_bytes());
+        expected[4..6].copy_from_slice(&3u16.to_ne_bytes());
+        expected[6] = 1;
+        assert_eq!(buf, expected);
+        unsafe {
+            a.write_entity_aos(1, &buf);
+        }
+        assert_eq!(*a.get::<u8>().unwrap(), [1, 1]);
+        assert_eq!(*a.get::<u32>().unwrap(), [2, 2]);
+        assert_eq!(*a.get::<u16>().unwrap(), [3, 3]);
     }
 
-    fn search(&self, id: &TypeId) -> Option<usize> {
-        self.0.binary_search_by_key(id, |(id, _)| *id).ok()
+    #[test]
+    fn memset_column() {
+        let mut a = archetype_of::<(u16, u8)>();
+        push(&mut a, 0, (1u16, 2u8));
+        push(&mut a, 1, (3u16, 4u8));
+        unsafe {
+            a.memset_column(TypeId::of::<u16>(), 0xab);
+        }
+        assert_eq!(*a.get::<u16>().unwrap(), [0xabab, 0xabab]);
+        assert_eq!(*a.get::<u8>().unwrap(), [2, 4]);
     }
 
-    fn contains_key(&self, id: &TypeId) -> bool {
-        self.search(id).is_some()
+    #[test]
+    fn replace_with() {
+        let rc = Arc::new(());
+        let mut a = archetype_of::<(u32, Arc<()>)>();
+        push(&mut a, 0, (1u32, rc.clone()));
+        a.set_user_data(7);
+        let generation = a.realloc_generation();
+        let mut b = archetype_of::<(u32, Arc<()>)>();
+        push(&mut b, 3, (4u32, Arc::new(())));
+        a.replace_with(b);
+        assert_eq!(Arc::strong_count(&rc), 1);
+        assert_eq!(a.user_data(), 7);
+        assert!(a.realloc_generation() > generation);
+        assert_eq!(a.ids(), [3]);
+        assert_eq!(*a.get::<u32>().unwrap(), [4]);
     }
 
-    fn get(&self, id: &TypeId) -> Option<&V> {
-        self.search(id).map(move |idx| &self.0[idx].1)
+    #[test]
+    #[should_panic(expected = "archetypes must have identical component types")]
+    fn replace_with_different_types() {
+        let mut a = archetype_of::<(u32,)>();
+        a.replace_with(archetype_of::<(u8,)>());
     }
-}
 
-/// Metadata required to store a component.
-///
-/// All told, this means a [`TypeId`], to be able to dynamically name/check the component type; a
-/// [`Layout`], so that we know how to allocate memory for this component type; and a drop function
-/// which internally calls [`core::ptr::drop_in_place`] with the correct type parameter.
-#[derive(Debug, Copy, Clone)]
-pub struct TypeInfo {
-    id: TypeId,
-    layout: Layout,
-    drop: unsafe fn(*mut u8),
-    #[cfg(debug_assertions)]
-    type_name: &'static str,
-}
+    #[test]
+    fn relocate() {
+        let rc = Arc::new(());
+        let mut a = archetype_of::<(u32, Arc<()>)>();
+        let mut b = archetype_of::<(u32,)>();
+        push(&mut b, 9, (0u32,));
+        for i in 0..3u32 {
+            push(&mut a, i, (i, rc.clone()));
+        }
+        assert_eq!(a.relocate(0, &mut b), (1, 0..1));
+        assert_eq!(Arc::strong_count(&rc), 3);
+        assert_eq!(a.ids(), [2, 1]);
+        assert_eq!(b.ids(), [9, 0]);
+        assert_eq!(*b.get::<u32>().unwrap(), [0, 0]);
+        assert_eq!(a.relocate(1, &mut b), (2, 1..1));
+        assert_eq!(*b.get::<u32>().unwrap(), [0, 0, 1]);
+    }
 
-impl TypeInfo {
-    /// Construct a `TypeInfo` directly from the static type.
-    pub fn of<T: 'static>() -> Self {
-        unsafe fn drop_ptr<T>(x: *mut u8) {
-            x.cast::<T>().drop_in_place()
+    #[test]
+    #[should_panic(expected = "target archetype has components absent from this archetype")]
+    fn relocate_superset() {
+        let mut a = archetype_of::<(u32,)>();
+        let mut b = archetype_of::<(u32, bool)>();
+        push(&mut a, 0, (0u32,));
+        a.relocate(0, &mut b);
+    }
+
+    #[test]
+    fn assert_consistent() {
+        let mut a = archetype_of::<(u32, bool)>();
+        a.assert_consistent();
+        push(&mut a, 0, (1u32, true));
+        unsafe {
+            a.add_column(TypeInfo::of::<u8>());
         }
+        a.assert_consistent();
+        a.remove_column(TypeId::of::<u8>());
+        a.remove_column(TypeId::of::<u32>());
+        a.assert_consistent();
+        a.clear();
+        a.assert_consistent();
+    }
 
-        Self {
-            id: TypeId::of::<T>(),
-            layout: Layout::new::<T>(),
-            drop: drop_ptr::<T>,
-            #[cfg(debug_assertions)]
-            type_name: core::any::type_name::<T>(),
+    #[test]
+    fn read_any() {
+        use crate::alloc::string::String;
+
+        let rc = Arc::new(());
+        let mut a = archetype_of::<(String, Arc<()>)>();
+        push(&mut a, 0, (String::from("a"), rc.clone()));
+        push(&mut a, 1, (String::from("b"), rc.clone()));
+        let (value, moved) = unsafe {
+            a.read_any(TypeId::of::<String>(), 0, |ptr| {
+                Box::new(ptr::read(ptr.cast::<String>()))
+            })
+        };
+        assert_eq!(value.downcast_ref::<String>().unwrap(), "a");
+        assert_eq!(moved, 0..1);
+        assert_eq!(Arc::strong_count(&rc), 2);
+        assert_eq!(&*a.get::<String>().unwrap(), ["b"]);
+    }
+
+    #[test]
+    fn count_where() {
+        let mut a = archetype_of::<(u32,)>();
+        for i in 0..5u32 {
+            push(&mut a, i, (i,));
         }
+        assert_eq!(a.count_where(|&x: &u32| x >= 2), 3);
+        assert_eq!(a.count_where(|_: &u8| true), 0);
     }
 
-    /// Construct a `TypeInfo` from its components. This is useful in the rare case that you have
-    /// some kind of pointer to raw bytes/erased memory holding a component type, coming from a
-    /// source unrelated to hecs, and you want to treat it as an insertable component by
-    /// implementing the `DynamicBundle` API.
-    pub fn from_parts(id: TypeId, layout: Layout, drop: unsafe fn(*mut u8)) -> Self {
-        Self {
-            id,
-            layout,
-            drop,
-            #[cfg(debug_assertions)]
-            type_name: "<unknown> (TypeInfo constructed from parts)",
+    #[test]
+    fn iter_rev() {
+        let rc = Arc::new(());
+        let mut a = archetype_of::<(u32, Arc<()>)>();
+        for i in 0..5u32 {
+            push(&mut a, i, (i, rc.clone()));
+        }
+        let doomed = a
+            .get::<u32>()
+            .unwrap()
+            .iter_rev()
+            .filter(|&(_, &x)| x % 2 == 1)
+            .map(|(index, _)| index)
+            .collect::<Vec<_>>();
+        assert_eq!(doomed, [3, 1]);
+        for index in doomed {
+            unsafe {
+                a.remove(index, true);
+            }
         }
+        assert_eq!(Arc::strong_count(&rc), 4);
+        assert_eq!(*a.get::<u32>().unwrap(), [0, 4, 2]);
     }
 
-    /// Access the `TypeId` for this component type.
-    pub fn id(&self) -> TypeId {
-        self.id
+    #[test]
+    fn truncate_dead() {
+        let rc = Arc::new(());
+        let mut a = archetype_of::<(u32, Arc<()>)>();
+        for i in 0..4u32 {
+            push(&mut a, i, (i, rc.clone()));
+        }
+        unsafe {
+            a.mark_dead(1);
+            a.mark_dead(2);
+        }
+        assert_eq!(Arc::strong_count(&rc), 3);
+        a.truncate(2);
+        assert_eq!(Arc::strong_count(&rc), 2);
+        a.compact();
+        assert_eq!(a.ids(), [0]);
+        assert_eq!(Arc::strong_count(&rc), 2);
     }
 
-    /// Access the `Layout` of this component type.
-    pub fn layout(&self) -> Layout {
-        self.layout
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "archetype has 65 component types, exceeding the limit of 64")]
+    fn add_column_limit() {
+        macro_rules! pairs {
+            ($($a:ty),*) => {
+                [$(pairs!(@row $a; u8, u16, u32, u64, i8, i16, i32, i64, bool)),*].concat()
+            };
+            (@row $a:ty; $($b:ty),*) => {
+                vec![$(TypeInfo::of::<($a, $b)>()),*]
+            };
+        }
+        let types = pairs!(u8, u16, u32, u64, i8, i16, i32, i64, bool);
+        let mut a = archetype_of::<()>();
+        push(&mut a, 0, ());
+        for ty in types {
+            unsafe {
+                a.add_column(ty);
+            }
+        }
     }
 
-    /// Directly call the destructor on a pointer to data of this component type.
-    ///
-    /// # Safety
-    ///
-    /// All of the caveats of [`core::ptr::drop_in_place`] apply, with the additional requirement
-    /// that this method is being called on a pointer to an object of the correct component type.
-    pub unsafe fn drop(&self, data: *mut u8) {
-        (self.drop)(data)
+    #[test]
+    fn iter_mut_opt() {
+        let mut a = archetype_of::<(u32, u8)>();
+        push(&mut a, 0, (1u32, 2u8));
+        let mut columns = a.iter_mut_opt::<u32, u8>().unwrap();
+        for (x, y) in columns.iter_mut() {
+            *x += u32::from(*y.unwrap());
+        }
+        drop(columns);
+        assert_eq!(*a.get::<u32>().unwrap(), [3]);
+        let mut columns = a.iter_mut_opt::<u32, bool>().unwrap();
+        assert!(columns.iter_mut().all(|(_, y)| y.is_none()));
+        drop(columns);
+        assert!(a.iter_mut_opt::<bool, u8>().is_none());
     }
 
-    /// Get the function pointer encoding the destructor for the component type this `TypeInfo`
-    /// represents.
-    pub fn drop_shim(&self) -> unsafe fn(*mut u8) {
-        self.drop
+    #[test]
+    #[should_panic(expected = "requested more than once")]
+    fn iter_mut_opt_duplicate() {
+        let a = archetype_of::<(u32,)>();
+        a.iter_mut_opt::<u32, u32>();
     }
-}
 
-impl PartialOrd for TypeInfo {
-    fn partial_cmp(&self, other: &Self) -> Option<core::cmp::Ordering> {
-        Some(self.cmp(other))
+    #[test]
+    fn snapshot_column() {
+        let mut a = archetype_of::<(u32,)>();
+        push(&mut a, 0, (1u32,));
+        push(&mut a, 1, (2u32,));
+        let snapshot = a.snapshot_column::<u32>();
+        unsafe {
+            a.remove(0, true);
+        }
+        assert_eq!(snapshot, [1, 2]);
+        assert!(a.snapshot_column::<u8>().is_empty());
     }
-}
 
-impl Ord for TypeInfo {
-    /// Order by alignment, descending. Ties broken with TypeId.
-    fn cmp(&self, other: &Self) -> core::cmp::Ordering {
-        self.layout
-            .align()
-            .cmp(&other.layout.align())
-            .reverse()
-            .then_with(|| self.id.cmp(&other.id))
+    #[test]
+    fn remove_column_dead() {
+        let rc = Arc::new(());
+        let mut a = archetype_of::<(u32, Arc<()>)>();
+        push(&mut a, 0, (0u32, rc.clone()));
+        push(&mut a, 1, (1u32, rc.clone()));
+        unsafe {
+            a.mark_dead(0);
+        }
+        assert_eq!(Arc::strong_count(&rc), 2);
+        assert!(a.remove_column(TypeId::of::<Arc<()>>()));
+        assert_eq!(Arc::strong_count(&rc), 1);
+        a.compact();
+        assert_eq!(a.ids(), [1]);
     }
-}
 
-impl PartialEq for TypeInfo {
-    fn eq(&self, other: &Self) -> bool {
-        self.id == other.id
+    #[test]
+    fn move_many_to() {
+        let mut a = archetype_of::<(u32,)>();
+        let mut b = archetype_of::<(u32, bool)>();
+        for i in 0..4 {
+            push(&mut a, i, (i * 10,));
+        }
+        push(&mut b, 7, (70u32, false));
+        unsafe {
+            a.move_many_to(&[0, 2], &mut b);
+            for index in 1..3 {
+                let mut flag = true;
+                b.put_dynamic(
+                    (&mut flag as *mut bool).cast(),
+                    TypeId::of::<bool>(),
+                    1,
+                    index,
+                );
+            }
+        }
+        assert_eq!(a.ids(), [3, 1]);
+        assert_eq!(*a.get::<u32>().unwrap(), [30, 10]);
+        assert_eq!(b.ids(), [7, 2, 0]);
+        assert_eq!(*b.get::<u32>().unwrap(), [70, 20, 0]);
+        assert_eq!(*b.get::<bool>().unwrap(), [false, true, true]);
     }
-}
 
-impl Eq for TypeInfo {}
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "structural change to a frozen archetype")]
+    fn frozen() {
+        let mut a = archetype_of::<(u32,)>();
+        push(&mut a, 0, (1u32,));
+        a.freeze();
+        a.thaw();
+        push(&mut a, 1, (2u32,));
+        a.freeze();
+        unsafe {
+            a.remove(0, true);
+        }
+    }
 
-/// Shared reference to a single column of component data in an [`Archetype`]
-pub struct ArchetypeColumn<'a, T: Component> {
-    archetype: &'a Archetype,
-    column: &'a [T],
-}
+    #[test]
+    fn data_eq() {
+        let mut a = archetype_of::<(u32, u8)>();
+        let mut b = archetype_of::<(u32, u8)>();
+        push(&mut a, 0, (1u32, 2u8));
+        push(&mut b, 0, (1u32, 2u8));
+        assert!(unsafe { a.data_eq(&b) });
+        push(&mut a, 1, (3u32, 4u8));
+        push(&mut b, 1, (3u32, 5u8));
+        assert!(unsafe { !a.data_eq(&b) });
+        assert!(unsafe { !a.data_eq(&archetype_of::<(u32,)>()) });
+    }
 
-impl<T: Component> Deref for ArchetypeColumn<'_, T> {
-    type Target = [T];
-    fn deref(&self) -> &[T] {
-        self.column
+    #[test]
+    fn truncate() {
+        let rc = Arc::new(());
+        let mut a = archetype_of::<(Arc<()>, u32)>();
+        for i in 0..3 {
+            push(&mut a, i, (rc.clone(), i));
+        }
+        a.truncate(1);
+        assert_eq!(Arc::strong_count(&rc), 2);
+        assert_eq!(a.ids(), [0]);
+        assert_eq!(*a.get::<u32>().unwrap(), [0]);
     }
-}
 
-impl<T: Component> Drop for ArchetypeColumn<'_, T> {
-    fn drop(&mut self) {
-        let state = self.archetype.get_state::<T>().unwrap();
-        self.archetype.release::<T>(state);
+    #[test]
+    fn drop_component() {
+        let rc = Arc::new(());
+        let mut a = archetype_of::<(Arc<()>, u32)>();
+        push(&mut a, 0, (rc.clone(), 0u32));
+        unsafe {
+            assert!(!a.drop_component(TypeId::of::<bool>(), 0));
+            assert!(a.drop_component(TypeId::of::<Arc<()>>(), 0));
+            assert_eq!(Arc::strong_count(&rc), 1);
+            a.remove(0, false);
+        }
+        assert!(a.is_empty());
     }
-}
 
-impl<T: Component> Clone for ArchetypeColumn<'_, T> {
-    fn clone(&self) -> Self {
-        let state = self.archetype.get_state::<T>().unwrap();
-        self.archetype.borrow::<T>(state);
-        Self {
-            archetype: self.archetype,
-            column: self.column,
+    #[test]
+    fn shrink_to_fit() {
+        let mut a = archetype_of::<(u32, ())>();
+        for i in 0..3 {
+            push(&mut a, i, (i, ()));
         }
+        a.shrink_to_fit();
+        assert_eq!(a.capacity(), 3);
+        assert_eq!(*a.get::<u32>().unwrap(), [0, 1, 2]);
+        a.truncate(0);
+        a.shrink_to_fit();
+        assert_eq!(a.capacity(), 0);
+        push(&mut a, 3, (3u32, ()));
+        assert_eq!(a.ids(), [3]);
+        assert_eq!(*a.get::<u32>().unwrap(), [3]);
     }
-}
 
-impl<T: Component + fmt::Debug> fmt::Debug for ArchetypeColumn<'_, T> {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        self.column.fmt(f)
+    #[test]
+    fn read_bundle() {
+        let rc = Arc::new(());
+        let mut a = archetype_of::<(u32, bool, Arc<()>)>();
+        push(&mut a, 0, (1u32, true, rc.clone()));
+        push(&mut a, 1, (2u32, false, rc.clone()));
+        assert!(a.read_bundle::<(u32, i8)>(0).is_none());
+        assert_eq!(a.len(), 2);
+        assert_eq!(a.read_bundle::<(bool, u32)>(0), Some((true, 1)));
+        assert_eq!(Arc::strong_count(&rc), 2);
+        assert_eq!(a.ids(), [1]);
+        assert_eq!(*a.get::<u32>().unwrap(), [2]);
+    }
+
+    #[test]
+    fn stable_order() {
+        let mut a = archetype_of::<(u32,)>();
+        a.set_stable_order(true);
+        for i in 0..4 {
+            push(&mut a, i, (i,));
+        }
+        assert_eq!(unsafe { a.remove(1, true) }, 1..3);
+        assert_eq!(a.ids(), [0, 2, 3]);
+        assert_eq!(*a.get::<u32>().unwrap(), [0, 2, 3]);
+        assert_eq!(unsafe { a.remove(2, true) }, 2..2);
+        assert_eq!(a.ids(), [0, 2]);
+    }
+
+    #[test]
+    #[cfg(feature = "metrics")]
+    fn metrics() {
+        let mut a = archetype_of::<(u32,)>();
+        for i in 0..65 {
+            push(&mut a, i, (i,));
+        }
+        unsafe {
+            a.remove(0, true);
+            a.move_to(0, |_, _, _| {});
+        }
+        let metrics = a.metrics();
+        assert_eq!(metrics.grows, 2);
+        assert_eq!(metrics.removes, 1);
+        assert_eq!(metrics.moves, 1);
+        assert_eq!(metrics.bytes_copied, 64 * 4 + 4 + 4 + 4);
+    }
+
+    #[test]
+    #[should_panic(expected = "identical component types")]
+    fn swap_data_mismatched() {
+        let mut a = archetype_of::<(u32,)>();
+        let mut b = archetype_of::<(u32, bool)>();
+        a.swap_data(&mut b);
     }
 }
@@ -1,4 +1,4 @@
-use crate::alloc::collections::BinaryHeap;
+use crate::alloc::vec::Vec;
 use core::{any::TypeId, fmt, mem::MaybeUninit, slice};
 
 use crate::{
@@ -9,7 +9,7 @@ use crate::{
 /// A collection of component types
 #[derive(Debug, Clone, Default)]
 pub struct ColumnBatchType {
-    types: BinaryHeap<TypeInfo>,
+    types: Vec<TypeInfo>,
 }
 
 impl ColumnBatchType {
@@ -20,16 +20,16 @@ impl ColumnBatchType {
 
     /// Update to include `T` components
     pub fn add<T: Component>(&mut self) -> &mut Self {
-        self.types.push(TypeInfo::of::<T>());
+        if !self.types.iter().any(|ty| ty.id() == TypeId::of::<T>()) {
+            self.types.push(TypeInfo::of::<T>());
+        }
         self
     }
 
     /// Construct a [`ColumnBatchBuilder`] for *exactly* `size` entities with these components
     pub fn into_batch(self, size: u32) -> ColumnBatchBuilder {
-        let mut types = self.types.into_sorted_vec();
-        types.dedup();
-        let fill = TypeIdMap::with_capacity_and_hasher(types.len(), Default::default());
-        let mut arch = Archetype::new(types);
+        let fill = TypeIdMap::with_capacity_and_hasher(self.types.len(), Default::default());
+        let mut arch = Archetype::new(self.types);
         arch.reserve(size);
         ColumnBatchBuilder {
             fill,
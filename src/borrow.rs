@@ -54,6 +54,11 @@ impl AtomicBorrow {
             .is_ok()
     }
 
+    /// Whether any borrow, shared or unique, is currently held
+    pub fn is_borrowed(&self) -> bool {
+        self.0.load(Ordering::Relaxed) != 0
+    }
+
     pub fn release(&self) {
         let value = self.0.fetch_sub(1, Ordering::Release);
         debug_assert!(value != 0, "unbalanced release");
@@ -84,6 +89,19 @@ mod tests {
         counter.borrow();
     }
 
+    #[test]
+    fn test_is_borrowed() {
+        let counter = AtomicBorrow::new();
+        assert!(!counter.is_borrowed());
+        assert!(counter.borrow());
+        assert!(counter.is_borrowed());
+        counter.release();
+        assert!(counter.borrow_mut());
+        assert!(counter.is_borrowed());
+        counter.release_mut();
+        assert!(!counter.is_borrowed());
+    }
+
     #[test]
     fn test_borrow() {
         let counter = AtomicBorrow::new();
@@ -32,6 +32,13 @@ pub unsafe trait DynamicBundle {
     /// Obtain the fields' TypeInfos, sorted by descending alignment then id
     #[doc(hidden)]
     fn type_info(&self) -> Vec<TypeInfo>;
+
+    /// Obtain the fields' TypeInfos in storage order, failing if any type occurs more than once
+    #[doc(hidden)]
+    fn sorted_type_info(&self) -> Result<Vec<TypeInfo>, DuplicateComponent> {
+        sort_type_info(self.type_info())
+    }
+
     /// Allow a callback to move all components out of the bundle
     ///
     /// Must invoke `f` only with a valid pointer and the pointee's type and size.
@@ -114,6 +121,42 @@ impl fmt::Display for MissingComponent {
 #[cfg(feature = "std")]
 impl std::error::Error for MissingComponent {}
 
+/// Sort `info` into storage order, failing if any type occurs more than once
+///
+/// The canonical form of a set of component types, relied upon by archetype storage.
+pub(crate) fn sort_type_info(mut info: Vec<TypeInfo>) -> Result<Vec<TypeInfo>, DuplicateComponent> {
+    info.sort_unstable();
+    match info.windows(2).find(|x| x[0].id() == x[1].id()) {
+        Some(x) => Err(DuplicateComponent(x[0].name())),
+        None => Ok(info),
+    }
+}
+
+/// Error indicating that a collection of components contained the same type more than once
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct DuplicateComponent(Option<&'static str>);
+
+impl DuplicateComponent {
+    /// Name of the duplicated type, if known
+    ///
+    /// Only tracked in debug builds.
+    pub(crate) fn name(&self) -> Option<&'static str> {
+        self.0
+    }
+}
+
+impl fmt::Display for DuplicateComponent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.0 {
+            Some(name) => write!(f, "duplicate {} component", name),
+            None => f.write_str("duplicate component"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for DuplicateComponent {}
+
 macro_rules! tuple_impl {
     ($($name: ident),*) => {
         unsafe impl<$($name: Component),*> DynamicBundle for ($($name,)*) {
@@ -0,0 +1,51 @@
+//! Per-thread counts of component drops, for detecting leaks and double drops in tests
+//!
+//! Only drops performed by hecs itself are counted, e.g. when an entity is despawned or a
+//! component is replaced. Components moved out of a [`World`](crate::World) and then dropped by
+//! the application are not counted.
+
+use core::any::TypeId;
+use core::cell::RefCell;
+use std::collections::HashMap;
+
+std::thread_local! {
+    static DROPS: RefCell<HashMap<TypeId, usize>> = RefCell::new(HashMap::new());
+}
+
+pub(crate) fn record(ty: TypeId) {
+    DROPS.with(|drops| *drops.borrow_mut().entry(ty).or_insert(0) += 1);
+}
+
+/// Number of `T` components dropped by hecs on this thread since the last [`reset`]
+pub fn drop_count<T: 'static>() -> usize {
+    DROPS.with(|drops| drops.borrow().get(&TypeId::of::<T>()).copied().unwrap_or(0))
+}
+
+/// Reset all counts for this thread to zero
+pub fn reset() {
+    DROPS.with(|drops| drops.borrow_mut().clear());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::World;
+
+    #[test]
+    fn counts() {
+        struct Foo;
+        reset();
+        let mut world = World::new();
+        let a = world.spawn((Foo, 1));
+        world.spawn((Foo, 2));
+        world.spawn((Foo,));
+        world.despawn(a).unwrap();
+        assert_eq!(drop_count::<Foo>(), 1);
+        assert_eq!(drop_count::<i32>(), 1);
+        drop(world);
+        assert_eq!(drop_count::<Foo>(), 3);
+        assert_eq!(drop_count::<i32>(), 2);
+        reset();
+        assert_eq!(drop_count::<Foo>(), 0);
+    }
+}
@@ -60,6 +60,11 @@ impl EntityBuilder {
         self
     }
 
+    /// Add the component of type `ty` at `ptr`, taking ownership of it
+    pub(crate) unsafe fn add_dynamic(&mut self, ptr: *mut u8, ty: TypeInfo) {
+        self.inner.add(ptr, ty, ());
+    }
+
     /// Construct a `Bundle` suitable for spawning
     pub fn build(&mut self) -> BuiltEntity<'_> {
         self.inner.info.sort_unstable_by_key(|x| x.0);
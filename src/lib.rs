@@ -62,6 +62,9 @@ mod borrow;
 mod bundle;
 mod column;
 mod command_buffer;
+#[cfg(feature = "drop-trace")]
+#[cfg_attr(docsrs, doc(cfg(feature = "drop-trace")))]
+pub mod drop_trace;
 mod entities;
 mod entity_builder;
 mod entity_ref;
@@ -69,14 +72,17 @@ mod entity_ref;
 mod parallel;
 mod query;
 mod query_one;
-#[cfg(any(feature = "row-serialize", feature = "column-serialize"))]
+#[cfg(feature = "serde")]
 pub mod serialize;
 mod take;
 mod world;
 
-pub use archetype::{Archetype, ArchetypeColumn};
+pub use archetype::{
+    AccessToken, Archetype, ArchetypeColumn, ArchetypeColumnGuard, ArchetypeColumnMut,
+    ArchetypeGuard, BorrowConflict, ColumnWithOptional, OptionalColumn,
+};
 pub use batch::{BatchIncomplete, BatchWriter, ColumnBatch, ColumnBatchBuilder, ColumnBatchType};
-pub use bundle::{Bundle, DynamicBundle, DynamicBundleClone, MissingComponent};
+pub use bundle::{Bundle, DuplicateComponent, DynamicBundle, DynamicBundleClone, MissingComponent};
 pub use column::{Column, ColumnMut};
 pub use command_buffer::CommandBuffer;
 pub use entities::{Entity, NoSuchEntity};
@@ -94,6 +100,8 @@ pub use world::{
     SpawnColumnBatchIter, World,
 };
 
+#[cfg(feature = "metrics")]
+pub use archetype::ArchetypeMetrics;
 #[cfg(feature = "parallel-iterators")]
 pub use parallel::*;
 
@@ -225,11 +225,11 @@ impl ParallelIter {
                                 // All good.
                                 // Iterate the entities+components.
                                 let fetch = Q::Fetch::execute(archetype, archetype_state);
-                                let entities = archetype.entities().as_ptr();
+                                let ids = archetype.ids();
 
                                 for index in start..end {
                                     unsafe {
-                                        let entity = *entities.add(index);
+                                        let entity = *ids.get_unchecked(index);
                                         let entity = Entity {
                                             id: entity,
                                             generation: meta
@@ -296,11 +296,11 @@ impl ParallelIter {
                                 // All good.
                                 // Iterate the entities+components.
                                 let fetch = Q::Fetch::execute(archetype, archetype_state);
-                                let entities = archetype.entities().as_ptr();
+                                let ids = archetype.ids();
 
                                 for index in start..end {
                                     unsafe {
-                                        let entity = *entities.add(index);
+                                        let entity = *ids.get_unchecked(index);
                                         let entity = Entity {
                                             id: entity,
                                             generation: meta
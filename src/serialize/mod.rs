@@ -14,3 +14,5 @@ pub mod column;
 #[cfg(feature = "row-serialize")]
 #[cfg_attr(docsrs, doc(cfg(feature = "row-serialize")))]
 pub mod row;
+#[cfg_attr(docsrs, doc(cfg(feature = "serde")))]
+pub mod snapshot;
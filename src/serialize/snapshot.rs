@@ -0,0 +1,504 @@
+//! Raw snapshots of individual archetypes
+//!
+//! An [`ArchetypeSnapshot`] captures an archetype's entity IDs and the raw bytes of each of its
+//! columns, identifying component types by application-defined stable IDs rather than
+//! [`TypeId`]s, which may change between builds. This is only meaningful for plain-data
+//! components which contain no pointers or references, whose bytes fully describe their value.
+//! Components containing padding must not be snapshotted at all, as their padding bytes are
+//! uninitialized.
+//!
+//! See [`Archetype::to_snapshot()`](crate::Archetype::to_snapshot) and
+//! [`Archetype::from_snapshot()`](crate::Archetype::from_snapshot).
+//!
+//! In terms of the serde data model, a snapshot is a 3-tuple of a sequence of `u64` component
+//! IDs, a sequence of `u32` entity IDs, and a sequence of byte arrays, one per component ID.
+
+use crate::alloc::vec::Vec;
+use core::any::TypeId;
+use core::fmt;
+use core::marker::PhantomData;
+use core::ops::Range;
+
+use serde::{
+    de::{self, DeserializeSeed, SeqAccess, Visitor},
+    ser::{SerializeSeq, SerializeTuple},
+    Deserialize, Deserializer, Serialize, Serializer,
+};
+
+use crate::TypeInfo;
+
+/// Owned copy of an archetype's contents
+///
+/// Produced by [`Archetype::to_snapshot()`](crate::Archetype::to_snapshot).
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct ArchetypeSnapshot {
+    /// Stable IDs of the stored component types
+    pub type_ids: Vec<u64>,
+    /// IDs of the stored entities
+    pub entities: Vec<u32>,
+    /// Raw bytes of each component type's column, in the same order as `type_ids`
+    pub columns: Vec<Vec<u8>>,
+}
+
+/// Maps component types to and from the stable IDs used in [`ArchetypeSnapshot`]s
+pub trait SnapshotRegistry {
+    /// Stable ID of the component type identified by `ty`, or `None` to omit it from snapshots
+    fn stable_id(&self, ty: TypeId) -> Option<u64>;
+
+    /// Layout and destructor of the component type with the stable ID `id`, if known
+    fn type_info(&self, id: u64) -> Option<TypeInfo>;
+}
+
+/// Identifies each listed component type by its [`TypeInfo::stable_hash()`]
+///
+/// Types with a stable hash of 0, such as those constructed by [`TypeInfo::from_parts()`] without
+/// [`TypeInfo::with_stable_hash()`], are treated as unregistered, since 0 cannot tell them apart.
+impl SnapshotRegistry for [TypeInfo] {
+    fn stable_id(&self, ty: TypeId) -> Option<u64> {
+        self.iter()
+            .find(|info| info.id() == ty)
+            .map(|info| info.stable_hash())
+            .filter(|&id| id != 0)
+    }
+
+    fn type_info(&self, id: u64) -> Option<TypeInfo> {
+        if id == 0 {
+            return None;
+        }
+        self.iter().find(|info| info.stable_hash() == id).copied()
+    }
+}
+
+/// Error indicating that an [`ArchetypeSnapshot`] could not be restored
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SnapshotError {
+    /// The registry did not recognize a stable component type ID
+    UnknownType(u64),
+    /// A component type occurred more than once, or a column's length did not match the entity
+    /// count
+    Malformed,
+    /// The snapshot's component types differ from those of the archetype it was restored into
+    TypeMismatch,
+    /// The snapshot holds more entities than the ring archetype it was restored into can
+    CapacityExceeded,
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for SnapshotError {}
+
+impl fmt::Display for SnapshotError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            SnapshotError::UnknownType(id) => write!(f, "unknown component type {}", id),
+            SnapshotError::Malformed => f.write_str("malformed archetype snapshot"),
+            SnapshotError::TypeMismatch => f.write_str("snapshot component types differ"),
+            SnapshotError::CapacityExceeded => f.write_str("snapshot exceeds ring capacity"),
+        }
+    }
+}
+
+/// Find the components which differ between two versions of a snapshot column
+///
+/// `old` and `new` each hold `len` components of `stride` bytes, e.g. the same entry of
+/// [`ArchetypeSnapshot::columns`] from consecutive frames. Returns the index of each changed
+/// component with the range of its bytes which changed, suitable for delta compression.
+///
+/// # Panics
+///
+/// Panics if either buffer is not exactly `stride * len` bytes long.
+pub fn diff_columns(old: &[u8], new: &[u8], stride: usize, len: usize) -> Vec<(u32, Range<usize>)> {
+    assert_eq!(old.len(), stride * len, "old column length mismatch");
+    assert_eq!(new.len(), stride * len, "new column length mismatch");
+    if stride == 0 {
+        return Vec::new();
+    }
+    old.chunks_exact(stride)
+        .zip(new.chunks_exact(stride))
+        .enumerate()
+        .filter_map(|(index, (old, new))| {
+            let start = old.iter().zip(new).position(|(x, y)| x != y)?;
+            let end = stride
+                - old
+                    .iter()
+                    .rev()
+                    .zip(new.iter().rev())
+                    .position(|(x, y)| x != y)?;
+            Some((index as u32, start..end))
+        })
+        .collect()
+}
+
+impl Serialize for ArchetypeSnapshot {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let mut tuple = serializer.serialize_tuple(3)?;
+        tuple.serialize_element(&SerializeSeqOf(&self.type_ids))?;
+        tuple.serialize_element(&SerializeSeqOf(&self.entities))?;
+        tuple.serialize_element(&SerializeColumns(&self.columns))?;
+        tuple.end()
+    }
+}
+
+struct SerializeSeqOf<'a, T>(&'a [T]);
+
+impl<T: Serialize> Serialize for SerializeSeqOf<'_, T> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let mut seq = serializer.serialize_seq(Some(self.0.len()))?;
+        for x in self.0 {
+            seq.serialize_element(x)?;
+        }
+        seq.end()
+    }
+}
+
+struct SerializeColumns<'a>(&'a [Vec<u8>]);
+
+impl Serialize for SerializeColumns<'_> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let mut seq = serializer.serialize_seq(Some(self.0.len()))?;
+        for column in self.0 {
+            seq.serialize_element(&SerializeBytes(column))?;
+        }
+        seq.end()
+    }
+}
+
+struct SerializeBytes<'a>(&'a [u8]);
+
+impl Serialize for SerializeBytes<'_> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_bytes(self.0)
+    }
+}
+
+impl<'de> Deserialize<'de> for ArchetypeSnapshot {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_tuple(3, SnapshotVisitor)
+    }
+}
+
+struct SnapshotVisitor;
+
+impl<'de> Visitor<'de> for SnapshotVisitor {
+    type Value = ArchetypeSnapshot;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a 3-tuple of component IDs, entity IDs, and component columns")
+    }
+
+    fn visit_seq<A>(self, mut seq: A) -> Result<ArchetypeSnapshot, A::Error>
+    where
+        A: SeqAccess<'de>,
+    {
+        let type_ids = seq
+            .next_element_seed(DeserializeSeqOf(PhantomData))?
+            .ok_or_else(|| de::Error::invalid_length(0, &self))?;
+        let entities = seq
+            .next_element_seed(DeserializeSeqOf(PhantomData))?
+            .ok_or_else(|| de::Error::invalid_length(1, &self))?;
+        let columns = seq
+            .next_element_seed(DeserializeColumns)?
+            .ok_or_else(|| de::Error::invalid_length(2, &self))?;
+        Ok(ArchetypeSnapshot {
+            type_ids,
+            entities,
+            columns,
+        })
+    }
+}
+
+struct DeserializeSeqOf<T>(PhantomData<T>);
+
+impl<'de, T: Deserialize<'de>> DeserializeSeed<'de> for DeserializeSeqOf<T> {
+    type Value = Vec<T>;
+
+    fn deserialize<D>(self, deserializer: D) -> Result<Vec<T>, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_seq(self)
+    }
+}
+
+impl<'de, T: Deserialize<'de>> Visitor<'de> for DeserializeSeqOf<T> {
+    type Value = Vec<T>;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a sequence")
+    }
+
+    fn visit_seq<A>(self, mut seq: A) -> Result<Vec<T>, A::Error>
+    where
+        A: SeqAccess<'de>,
+    {
+        let mut out = Vec::with_capacity(seq.size_hint().unwrap_or(0));
+        while let Some(x) = seq.next_element()? {
+            out.push(x);
+        }
+        Ok(out)
+    }
+}
+
+struct DeserializeColumns;
+
+impl<'de> DeserializeSeed<'de> for DeserializeColumns {
+    type Value = Vec<Vec<u8>>;
+
+    fn deserialize<D>(self, deserializer: D) -> Result<Vec<Vec<u8>>, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_seq(self)
+    }
+}
+
+impl<'de> Visitor<'de> for DeserializeColumns {
+    type Value = Vec<Vec<u8>>;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a sequence of component columns")
+    }
+
+    fn visit_seq<A>(self, mut seq: A) -> Result<Vec<Vec<u8>>, A::Error>
+    where
+        A: SeqAccess<'de>,
+    {
+        let mut out = Vec::with_capacity(seq.size_hint().unwrap_or(0));
+        while let Some(column) = seq.next_element_seed(DeserializeBytes)? {
+            out.push(column);
+        }
+        Ok(out)
+    }
+}
+
+struct DeserializeBytes;
+
+impl<'de> DeserializeSeed<'de> for DeserializeBytes {
+    type Value = Vec<u8>;
+
+    fn deserialize<D>(self, deserializer: D) -> Result<Vec<u8>, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_bytes(self)
+    }
+}
+
+impl<'de> Visitor<'de> for DeserializeBytes {
+    type Value = Vec<u8>;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a component column")
+    }
+
+    fn visit_bytes<E>(self, bytes: &[u8]) -> Result<Vec<u8>, E>
+    where
+        E: de::Error,
+    {
+        Ok(bytes.to_vec())
+    }
+
+    fn visit_seq<A>(self, seq: A) -> Result<Vec<u8>, A::Error>
+    where
+        A: SeqAccess<'de>,
+    {
+        DeserializeSeqOf(PhantomData).visit_seq(seq)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_test::{assert_tokens, Token};
+
+    use super::*;
+    use crate::alloc::{alloc::Layout, sync::Arc, vec};
+    use crate::{Archetype, Bundle, DynamicBundle};
+
+    struct Registry;
+
+    impl SnapshotRegistry for Registry {
+        fn stable_id(&self, ty: TypeId) -> Option<u64> {
+            if ty == TypeId::of::<u32>() {
+                Some(1)
+            } else if ty == TypeId::of::<u16>() {
+                Some(2)
+            } else {
+                None
+            }
+        }
+
+        fn type_info(&self, id: u64) -> Option<TypeInfo> {
+            match id {
+                1 => Some(TypeInfo::of::<u32>()),
+                2 => Some(TypeInfo::of::<u16>()),
+                _ => None,
+            }
+        }
+    }
+
+    #[test]
+    fn roundtrip() {
+        let mut archetype =
+            <(u32, u16, bool)>::with_static_type_info(|info| Archetype::new(info.to_vec()));
+        for (id, components) in [(5, (1u32, 2u16, true)), (9, (3u32, 4u16, false))] {
+            unsafe {
+                let (index, _) = archetype.allocate(id);
+                components.put(|ptr, ty| {
+                    archetype.put_dynamic(ptr, ty.id(), ty.layout().size(), index);
+                });
+            }
+        }
+        let snapshot = unsafe { archetype.to_snapshot(&Registry) };
+        assert_eq!(snapshot.type_ids, [1, 2]);
+        assert_eq!(snapshot.entities, [5, 9]);
+        let restored = unsafe { Archetype::from_snapshot(&snapshot, &Registry) }.unwrap();
+        assert_eq!(restored.ids(), [5, 9]);
+        assert_eq!(*restored.get::<u32>().unwrap(), [1, 3]);
+        assert_eq!(*restored.get::<u16>().unwrap(), [2, 4]);
+        assert!(!restored.has::<bool>());
+    }
+
+    #[test]
+    fn stable_hash_registry() {
+        let mut archetype =
+            <(u32, bool)>::with_static_type_info(|info| Archetype::new(info.to_vec()));
+        unsafe {
+            let (index, _) = archetype.allocate(3);
+            (7u32, true).put(|ptr, ty| {
+                archetype.put_dynamic(ptr, ty.id(), ty.layout().size(), index);
+            });
+        }
+        let registry = [TypeInfo::of::<u32>()];
+        let snapshot = unsafe { archetype.to_snapshot(&registry[..]) };
+        assert_eq!(snapshot.type_ids, [TypeInfo::of::<u32>().stable_hash()]);
+        let restored = unsafe { Archetype::from_snapshot(&snapshot, &registry[..]) }.unwrap();
+        assert_eq!(*restored.get::<u32>().unwrap(), [7]);
+    }
+
+    #[test]
+    fn unhashed_registry() {
+        struct A;
+        struct B;
+        let registry = [
+            TypeInfo::from_parts(TypeId::of::<A>(), Layout::new::<u32>(), |_| {}),
+            TypeInfo::from_parts(TypeId::of::<B>(), Layout::new::<u32>(), |_| {}),
+        ];
+        let mut archetype = Archetype::new(registry.to_vec());
+        unsafe {
+            let (index, _) = archetype.allocate(0);
+            archetype.put_dynamic((&mut 1u32 as *mut u32).cast(), TypeId::of::<A>(), 4, index);
+            archetype.put_dynamic((&mut 2u32 as *mut u32).cast(), TypeId::of::<B>(), 4, index);
+        }
+        assert_eq!(registry[..].type_info(0), None);
+        let snapshot = unsafe { archetype.to_snapshot(&registry[..]) };
+        assert!(snapshot.type_ids.is_empty());
+        let forged = ArchetypeSnapshot {
+            type_ids: vec![0, 0],
+            entities: vec![0],
+            columns: vec![vec![0; 4], vec![0; 4]],
+        };
+        let result = unsafe { archetype.restore_from(&forged, &registry[..]) };
+        assert_eq!(result, Err(SnapshotError::TypeMismatch));
+    }
+
+    #[test]
+    fn restore() {
+        let rc = Arc::new(());
+        let mut archetype =
+            <(u32, u16, Arc<()>)>::with_static_type_info(|info| Archetype::new(info.to_vec()));
+        unsafe {
+            let (index, _) = archetype.allocate(1);
+            (1u32, 2u16, rc.clone()).put(|ptr, ty| {
+                archetype.put_dynamic(ptr, ty.id(), ty.layout().size(), index);
+            });
+        }
+        let snapshot = ArchetypeSnapshot {
+            type_ids: vec![2, 1],
+            entities: vec![4, 6],
+            columns: vec![vec![0; 4], vec![0; 8]],
+        };
+        let result = unsafe { archetype.restore_from(&snapshot, &Registry) };
+        assert_eq!(result, Err(SnapshotError::TypeMismatch));
+        assert_eq!(archetype.ids(), [1]);
+
+        let mut archetype =
+            <(u32, u16)>::with_static_type_info(|info| Archetype::new(info.to_vec()));
+        unsafe { archetype.restore_from(&snapshot, &Registry) }.unwrap();
+        assert_eq!(archetype.ids(), [4, 6]);
+        assert_eq!(*archetype.get::<u32>().unwrap(), [0, 0]);
+        let capacity = archetype.capacity();
+        let snapshot = unsafe { archetype.to_snapshot(&Registry) };
+        unsafe { archetype.restore_from(&snapshot, &Registry) }.unwrap();
+        assert_eq!(archetype.capacity(), capacity);
+        assert_eq!(unsafe { archetype.to_snapshot(&Registry) }, snapshot);
+
+        let mut ring =
+            Archetype::with_ring_capacity(vec![TypeInfo::of::<u32>(), TypeInfo::of::<u16>()], 1);
+        let result = unsafe { ring.restore_from(&snapshot, &Registry) };
+        assert_eq!(result, Err(SnapshotError::CapacityExceeded));
+        assert_eq!(ring.capacity(), 1);
+    }
+
+    #[test]
+    fn diff() {
+        let old = [0, 0, 0, 0, 1, 2, 3, 4, 5, 6, 7, 8];
+        let new = [0, 0, 0, 0, 1, 9, 9, 4, 5, 6, 7, 0];
+        assert_eq!(diff_columns(&old, &new, 4, 3), [(1, 1..3), (2, 3..4)]);
+        assert_eq!(diff_columns(&old, &old, 4, 3), []);
+        assert_eq!(diff_columns(&[], &[], 0, 3), []);
+    }
+
+    #[test]
+    fn invalid() {
+        let mut snapshot = ArchetypeSnapshot {
+            type_ids: vec![3],
+            entities: vec![0],
+            columns: vec![vec![0; 4]],
+        };
+        let result = unsafe { Archetype::from_snapshot(&snapshot, &Registry) };
+        assert_eq!(result.err(), Some(SnapshotError::UnknownType(3)));
+        snapshot.type_ids[0] = 2;
+        let result = unsafe { Archetype::from_snapshot(&snapshot, &Registry) };
+        assert_eq!(result.err(), Some(SnapshotError::Malformed));
+    }
+
+    #[test]
+    fn tokens() {
+        let snapshot = ArchetypeSnapshot {
+            type_ids: vec![7],
+            entities: vec![1, 2],
+            columns: vec![vec![3, 4]],
+        };
+        assert_tokens(
+            &snapshot,
+            &[
+                Token::Tuple { len: 3 },
+                Token::Seq { len: Some(1) },
+                Token::U64(7),
+                Token::SeqEnd,
+                Token::Seq { len: Some(2) },
+                Token::U32(1),
+                Token::U32(2),
+                Token::SeqEnd,
+                Token::Seq { len: Some(1) },
+                Token::Bytes(&[3, 4]),
+                Token::SeqEnd,
+                Token::TupleEnd,
+            ],
+        );
+    }
+}
@@ -54,8 +54,10 @@ unsafe impl<'a> DynamicBundle for TakenEntity<'a> {
 
 impl Drop for TakenEntity<'_> {
     fn drop(&mut self) {
-        if let Some(moved) = unsafe { self.archetype.remove(self.index, self.drop) } {
-            self.entities.meta[moved as usize].location.index = self.index;
+        for index in unsafe { self.archetype.remove(self.index, self.drop) } {
+            self.entities.meta[self.archetype.ids()[index as usize] as usize]
+                .location
+                .index = index;
         }
         self.entities.free(self.entity).unwrap();
     }
@@ -13,7 +13,7 @@ use core::hash::{BuildHasherDefault, Hasher};
 use core::marker::PhantomData;
 use spin::Mutex;
 
-use core::{fmt, ptr};
+use core::fmt;
 
 #[cfg(feature = "std")]
 use std::error::Error;
@@ -145,10 +145,11 @@ impl World {
 
         let loc = self.entities.alloc_at(handle);
         if let Some(loc) = loc {
-            if let Some(moved) = unsafe {
-                self.archetypes.archetypes[loc.archetype as usize].remove(loc.index, true)
-            } {
-                self.entities.meta[moved as usize].location.index = loc.index;
+            let archetype = &mut self.archetypes.archetypes[loc.archetype as usize];
+            for index in unsafe { archetype.remove(loc.index, true) } {
+                self.entities.meta[archetype.ids()[index as usize] as usize]
+                    .location
+                    .index = index;
             }
         }
 
@@ -168,7 +169,7 @@ impl World {
 
         let archetype = &mut self.archetypes.archetypes[archetype_id as usize];
         unsafe {
-            let index = archetype.allocate(entity.id);
+            let (index, _) = archetype.allocate(entity.id);
             components.put(|ptr, ty| {
                 archetype.put_dynamic(ptr, ty.id(), ty.layout().size(), index);
             });
@@ -262,10 +263,11 @@ impl World {
         for &handle in handles {
             let loc = self.entities.alloc_at(handle);
             if let Some(loc) = loc {
-                if let Some(moved) = unsafe {
-                    self.archetypes.archetypes[loc.archetype as usize].remove(loc.index, true)
-                } {
-                    self.entities.meta[moved as usize].location.index = loc.index;
+                let archetype = &mut self.archetypes.archetypes[loc.archetype as usize];
+                for index in unsafe { archetype.remove(loc.index, true) } {
+                    self.entities.meta[archetype.ids()[index as usize] as usize]
+                        .location
+                        .index = index;
                 }
             }
         }
@@ -311,10 +313,11 @@ impl World {
     pub fn despawn(&mut self, entity: Entity) -> Result<(), NoSuchEntity> {
         self.flush();
         let loc = self.entities.free(entity)?;
-        if let Some(moved) =
-            unsafe { self.archetypes.archetypes[loc.archetype as usize].remove(loc.index, true) }
-        {
-            self.entities.meta[moved as usize].location.index = loc.index;
+        let archetype = &mut self.archetypes.archetypes[loc.archetype as usize];
+        for index in unsafe { archetype.remove(loc.index, true) } {
+            self.entities.meta[archetype.ids()[index as usize] as usize]
+                .location
+                .index = index;
         }
         Ok(())
     }
@@ -651,7 +654,7 @@ impl World {
             );
 
             // Allocate storage in the archetype and update the entity's location to address it
-            let target_index = target_arch.allocate(entity.id);
+            let (target_index, _) = target_arch.allocate(entity.id);
             let meta = &mut self.entities.meta[entity.id as usize];
             meta.location.archetype = target.index;
             meta.location.index = target_index;
@@ -670,8 +673,10 @@ impl World {
             }
 
             // Free storage in the old archetype
-            if let Some(moved) = source_arch.remove(loc.index, false) {
-                self.entities.meta[moved as usize].location.index = loc.index;
+            for index in source_arch.remove(loc.index, false) {
+                self.entities.meta[source_arch.ids()[index as usize] as usize]
+                    .location
+                    .index = index;
             }
         }
         Ok(())
@@ -732,18 +737,21 @@ impl World {
                 loc.archetype as usize,
                 target as usize,
             );
-            let target_index = unsafe { target_arch.allocate(entity.id) };
+            let (target_index, _) = unsafe { target_arch.allocate(entity.id) };
             loc.archetype = target;
             loc.index = target_index;
-            if let Some(moved) = unsafe {
+            let moved = unsafe {
                 source_arch.move_to(old_index, |src, ty, size| {
                     // Only move the components present in the target archetype, i.e. the non-removed ones.
-                    if let Some(dst) = target_arch.get_dynamic(ty, size, target_index) {
-                        ptr::copy_nonoverlapping(src, dst.as_ptr(), size);
+                    if target_arch.has_dynamic(ty) {
+                        target_arch.put_dynamic(src, ty, size, target_index);
                     }
                 })
-            } {
-                self.entities.meta[moved as usize].location.index = old_index;
+            };
+            for index in moved {
+                self.entities.meta[source_arch.ids()[index as usize] as usize]
+                    .location
+                    .index = index;
             }
         }
 
@@ -872,7 +880,7 @@ impl World {
     pub fn flush(&mut self) {
         let arch = &mut self.archetypes.archetypes[0];
         self.entities
-            .flush(|id, location| location.index = unsafe { arch.allocate(id) });
+            .flush(|id, location| location.index = unsafe { arch.allocate(id).0 });
     }
 
     /// Inspect the archetypes that entities are organized into
@@ -1183,7 +1191,7 @@ where
     fn next(&mut self) -> Option<Entity> {
         let components = self.inner.next()?;
         let entity = self.entities.alloc();
-        let index = unsafe { self.archetype.allocate(entity.id) };
+        let (index, _) = unsafe { self.archetype.allocate(entity.id) };
         unsafe {
             components.put(|ptr, ty| {
                 self.archetype
@@ -795,6 +795,395 @@ fn columnar_access() {
     assert_eq!(*b.get::<i32>().unwrap(), [456, 789]);
 }
 
+#[test]
+fn columnar_access_mut() {
+    let mut world = World::new();
+    world.spawn(("abc", 123));
+    world.spawn(("def", 456));
+    let a = world.archetypes().nth(1).unwrap();
+    {
+        let (mut numbers, names) = a.get_mut2::<i32, &str>().unwrap();
+        for (n, name) in numbers.iter_mut().zip(names.iter()) {
+            *n += name.len() as i32;
+        }
+    }
+    assert_eq!(*a.get::<i32>().unwrap(), [126, 459]);
+    assert!(a.get_mut2::<i32, bool>().is_none());
+    assert!(a.get_mut::<i32>().is_some());
+}
+
+#[test]
+fn columnar_access_raw() {
+    let mut world = World::new();
+    world.spawn((1u16, 2u64));
+    world.spawn((3u16, 4u64));
+    let a = world.archetypes().nth(1).unwrap();
+    let (ptr, len, stride) = a.column_raw(std::any::TypeId::of::<u16>()).unwrap();
+    assert_eq!((len, stride), (2, 2));
+    let column = unsafe { std::slice::from_raw_parts(ptr.cast::<u16>(), len) };
+    assert_eq!(column, [1, 3]);
+    assert!(a.column_raw(std::any::TypeId::of::<bool>()).is_none());
+}
+
+#[test]
+fn columnar_access_with_id() {
+    let mut world = World::new();
+    let e = world.spawn((1,));
+    let f = world.spawn((2,));
+    world.despawn(e).unwrap();
+    let g = world.spawn((3,));
+    let a = world.archetypes().nth(1).unwrap();
+    let column = a.get::<i32>().unwrap();
+    let items = column.iter_with_id().collect::<Vec<_>>();
+    assert_eq!(items, [(f.id(), &2), (g.id(), &3)]);
+}
+
+#[test]
+fn columnar_access_mut_with_id() {
+    let mut world = World::new();
+    let e = world.spawn((1,));
+    let f = world.spawn((2,));
+    let a = world.archetypes().nth(1).unwrap();
+    let mut column = a.get_mut::<i32>().unwrap();
+    for (id, x) in column.iter_mut_with_id() {
+        *x += id as i32 * 10;
+    }
+    drop(column);
+    assert_eq!(*world.get::<i32>(e).unwrap(), 1 + e.id() as i32 * 10);
+    assert_eq!(*world.get::<i32>(f).unwrap(), 2 + f.id() as i32 * 10);
+}
+
+#[test]
+fn columnar_access_try() {
+    let mut world = World::new();
+    world.spawn((1,));
+    let a = world.archetypes().nth(1).unwrap();
+    let column = a.try_get::<i32>().unwrap().unwrap();
+    assert!(a.try_get::<i32>().unwrap().is_some());
+    let err = a.try_get_mut::<i32>().unwrap_err();
+    assert_eq!(err, BorrowConflict::new::<i32>());
+    assert_eq!(err.to_string(), "i32 already borrowed");
+    drop(column);
+    let column = a.try_get_mut::<i32>().unwrap().unwrap();
+    assert!(a.try_get::<i32>().is_err());
+    assert!(a.try_get::<u8>().unwrap().is_none());
+    drop(column);
+    assert!(a.try_get::<i32>().is_ok());
+}
+
+#[test]
+fn columnar_access_enumerate() {
+    let mut world = World::new();
+    let e = world.spawn((1,));
+    let f = world.spawn((2,));
+    let a = world.archetypes().nth(1).unwrap();
+    let column = a.get::<i32>().unwrap();
+    let items = column.enumerate().collect::<Vec<_>>();
+    assert_eq!(items, [(e.id(), 0, &1), (f.id(), 1, &2)]);
+    let range = a.column_range::<i32>(1, 2).unwrap();
+    let items = range.enumerate().collect::<Vec<_>>();
+    assert_eq!(items, [(f.id(), 1, &2)]);
+}
+
+#[test]
+fn sorted_type_info() {
+    let info = (1u8, 2u64, 3u32).sorted_type_info().unwrap();
+    let aligns = info.iter().map(|x| x.layout().align()).collect::<Vec<_>>();
+    assert_eq!(aligns, [8, 4, 1]);
+    let err = (1, true, 2).sorted_type_info().unwrap_err();
+    #[cfg(debug_assertions)]
+    assert_eq!(err.to_string(), "duplicate i32 component");
+    #[cfg(not(debug_assertions))]
+    assert_eq!(err.to_string(), "duplicate component");
+}
+
+#[test]
+fn columnar_access_range() {
+    let mut world = World::new();
+    let ids = (0..5).map(|i| world.spawn((i,)).id()).collect::<Vec<_>>();
+    let a = world.archetypes().nth(1).unwrap();
+    let range = a.column_range::<i32>(1, 3).unwrap();
+    assert_eq!(*range, [1, 2]);
+    let items = range.iter_with_id().map(|(id, _)| id).collect::<Vec<_>>();
+    assert_eq!(items, ids[1..3]);
+    assert_eq!(a.column_range::<i32>(5, 5).unwrap().len(), 0);
+    assert!(a.column_range::<i32>(3, 6).is_none());
+    assert!(a.column_range::<i32>(3, 2).is_none());
+}
+
+#[test]
+fn archetype_borrow_columns() {
+    use std::any::TypeId;
+
+    let mut world = World::new();
+    world.spawn((1, true));
+    let a = world.archetypes().nth(1).unwrap();
+    let (int, boolean) = (TypeId::of::<i32>(), TypeId::of::<bool>());
+    let guard = a.borrow_columns(&[int], &[boolean]).unwrap();
+    assert!(a.borrow_columns(&[boolean], &[]).is_none());
+    assert!(a.borrow_columns(&[int], &[]).is_some());
+    assert!(a.borrow_columns(&[int], &[TypeId::of::<u8>()]).is_none());
+    drop(guard);
+    assert!(a.borrow_columns(&[], &[int, boolean]).is_some());
+    assert!(a.borrow_columns(&[], &[int, int]).is_none());
+    assert!(a.get_mut2::<i32, bool>().is_some());
+}
+
+#[test]
+fn archetype_borrow_columns_elements() {
+    use std::any::TypeId;
+
+    let mut world = World::new();
+    world.spawn((1u16, true));
+    world.spawn((0x0203u16, false));
+    let a = world.archetypes().nth(1).unwrap();
+    let (short, boolean) = (TypeId::of::<u16>(), TypeId::of::<bool>());
+    let guard = a.borrow_columns(&[short], &[]).unwrap();
+    let elements = unsafe { guard.elements(short).unwrap().collect::<Vec<_>>() };
+    let expected: [(u32, &[u8]); 2] = [(0, &1u16.to_ne_bytes()), (1, &0x0203u16.to_ne_bytes())];
+    assert_eq!(elements, expected);
+    assert!(unsafe { guard.elements(boolean) }.is_none());
+}
+
+#[test]
+fn columnar_access_reinterpreted() {
+    #[derive(Debug, PartialEq)]
+    #[repr(C)]
+    struct Vec2 {
+        x: f32,
+        y: f32,
+    }
+
+    let mut world = World::new();
+    world.spawn(([1.0f32, 2.0],));
+    let a = world.archetypes().nth(1).unwrap();
+    let column = unsafe { a.column_as::<[f32; 2], Vec2>().unwrap() };
+    assert_eq!(*column, [Vec2 { x: 1.0, y: 2.0 }]);
+    drop(column);
+    assert!(a.get_mut::<[f32; 2]>().is_some());
+}
+
+#[test]
+#[should_panic(expected = "cannot be reinterpreted")]
+fn columnar_access_reinterpreted_mismatch() {
+    let mut world = World::new();
+    world.spawn(([1u8; 4],));
+    let a = world.archetypes().nth(1).unwrap();
+    let _ = unsafe { a.column_as::<[u8; 4], u32>() };
+}
+
+#[test]
+fn columnar_access_optional() {
+    let mut world = World::new();
+    world.spawn((1, true));
+    world.spawn((2, false));
+    let a = world.archetypes().nth(1).unwrap();
+    let present = a.optional_column::<i32>();
+    assert_eq!(present.iter().collect::<Vec<_>>(), [Some(&1), Some(&2)]);
+    let absent = a.optional_column::<u8>();
+    assert_eq!(absent.iter().collect::<Vec<_>>(), [None, None]);
+}
+
+#[test]
+fn archetype_get_guarded() {
+    let mut world = World::new();
+    world.spawn((1i32, true));
+    world.spawn((2i32, false));
+    let a = world.archetypes().nth(1).unwrap();
+    *a.get_guarded_mut::<i32>(1).unwrap() += 40;
+    {
+        let x = a.get_guarded::<i32>(1).unwrap();
+        assert_eq!(*x, 42);
+        assert_eq!(*a.get_guarded::<i32>(0).unwrap(), 1);
+    }
+    assert!(a.get_guarded::<i32>(2).is_none());
+    assert!(a.get_guarded::<u8>(0).is_none());
+    assert!(a.get_mut::<i32>().is_some());
+}
+
+#[test]
+fn columnar_access_split() {
+    let mut world = World::new();
+    world.spawn((1.0f32, 10.0f64));
+    world.spawn((2.0f32, 20.0f64));
+    let a = world.archetypes().nth(1).unwrap();
+    let (velocity, mut position) = a.split_columns::<f32, f64>().unwrap();
+    for (p, &v) in position.iter_mut().zip(velocity.iter()) {
+        *p += f64::from(v);
+    }
+    assert!(a.get::<f32>().is_some());
+    drop((velocity, position));
+    assert_eq!(*a.get::<f64>().unwrap(), [11.0, 22.0]);
+    assert!(a.split_columns::<f32, u8>().is_none());
+}
+
+#[test]
+#[should_panic(expected = "f32 requested more than once")]
+fn columnar_access_split_duplicate() {
+    let mut world = World::new();
+    world.spawn((1.0f32,));
+    let a = world.archetypes().nth(1).unwrap();
+    let _ = a.split_columns::<f32, f32>();
+}
+
+#[test]
+fn columnar_access_mut3() {
+    let mut world = World::new();
+    world.spawn((1.0f32, 2.0f64, 3u8));
+    world.spawn((4.0f32, 5.0f64, 6u8));
+    let a = world.archetypes().nth(1).unwrap();
+    {
+        let (mut pos, mut vel, acc) = a.get_mut3::<f32, f64, u8>().unwrap();
+        for ((p, v), a) in pos.iter_mut().zip(vel.iter_mut()).zip(acc.iter()) {
+            *v += f64::from(*a);
+            *p += *v as f32;
+        }
+    }
+    assert_eq!(*a.get::<f32>().unwrap(), [6.0, 15.0]);
+    assert!(a.get_mut3::<f32, f64, bool>().is_none());
+    assert!(a.get_mut::<f32>().is_some());
+}
+
+#[test]
+#[should_panic(expected = "u8 requested more than once")]
+fn columnar_access_mut3_duplicate() {
+    let mut world = World::new();
+    world.spawn((1.0f32, 3u8));
+    let a = world.archetypes().nth(1).unwrap();
+    let _ = a.get_mut3::<u8, f32, u8>();
+}
+
+#[test]
+fn archetype_find() {
+    let mut world = World::new();
+    let a = world.spawn(("alice", 1));
+    let b = world.spawn(("bob", 2));
+    let arch = world.archetypes().nth(1).unwrap();
+    let index = arch.find::<&str>(|&name| name == "bob").unwrap();
+    assert_eq!(arch.ids()[index as usize], b.id());
+    assert_eq!(
+        arch.find::<i32>(|&x| x == 1)
+            .map(|i| arch.ids()[i as usize]),
+        Some(a.id())
+    );
+    assert_eq!(arch.find::<i32>(|&x| x == 3), None);
+    assert_eq!(arch.find::<bool>(|_| true), None);
+}
+
+#[test]
+fn archetype_sorted_indices() {
+    let mut world = World::new();
+    for &(depth, name) in &[(3, "c"), (1, "a"), (2, "b"), (1, "d")] {
+        world.spawn((depth, name));
+    }
+    let a = world.archetypes().nth(1).unwrap();
+    let order = a.sorted_indices_by::<i32, _>(|&depth| depth).unwrap();
+    assert_eq!(order, [1, 3, 2, 0]);
+    let names = a.get::<&str>().unwrap();
+    assert_eq!(names[0], "c");
+    assert!(a.sorted_indices_by::<bool, _>(|&x| x).is_none());
+}
+
+#[test]
+fn archetype_entity_at() {
+    let mut world = World::new();
+    let a = world.spawn((1,));
+    let b = world.spawn((2,));
+    world.despawn(a).unwrap();
+    let arch = world.archetypes().nth(1).unwrap();
+    assert_eq!(arch.entity_at(0), Some(b.id()));
+    assert_eq!(arch.entity_at(1), None);
+}
+
+#[test]
+fn columnar_access_filtered() {
+    let mut world = World::new();
+    for i in 0..6 {
+        world.spawn((i,));
+    }
+    let a = world.archetypes().nth(1).unwrap();
+    let column = a.column_range::<i32>(1, 6).unwrap();
+    let odd = column.iter_filtered(|&x| x % 2 == 1).collect::<Vec<_>>();
+    assert_eq!(odd, [(1, &1), (3, &3), (5, &5)]);
+    assert_eq!(column.iter_filtered(|_| false).count(), 0);
+}
+
+#[test]
+fn columnar_access_2d() {
+    let mut world = World::new();
+    world.spawn(([1.0f32, 2.0, 3.0],));
+    world.spawn(([4.0f32, 5.0, 6.0],));
+    let a = world.archetypes().nth(1).unwrap();
+    let column = a.get::<[f32; 3]>().unwrap();
+    let (flat, rows, cols) = column.as_2d();
+    assert_eq!((rows, cols), (2, 3));
+    assert_eq!(flat, [1.0, 2.0, 3.0, 4.0, 5.0, 6.0]);
+}
+
+#[test]
+fn columnar_access_pairs() {
+    let mut world = World::new();
+    for i in 0..4 {
+        world.spawn((i,));
+    }
+    let a = world.archetypes().nth(1).unwrap();
+    let column = a.get::<i32>().unwrap();
+    let pairs = column
+        .pairs()
+        .map(|((i, &x), (j, &y))| (i, x, j, y))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        pairs,
+        [
+            (0, 0, 1, 1),
+            (0, 0, 2, 2),
+            (0, 0, 3, 3),
+            (1, 1, 2, 2),
+            (1, 1, 3, 3),
+            (2, 2, 3, 3)
+        ]
+    );
+    drop(column);
+    assert_eq!(a.column_range::<i32>(1, 3).unwrap().pairs().count(), 1);
+    assert_eq!(
+        a.column_range::<i32>(1, 3).unwrap().pairs().next(),
+        Some(((1, &1), (2, &2)))
+    );
+}
+
+#[test]
+fn archetype_get_mut_tokened() {
+    let mut world = World::new();
+    world.spawn((1, true));
+    world.spawn((2, false));
+    let a = world.archetypes().nth(1).unwrap();
+    let token = unsafe { AccessToken::new() };
+    unsafe {
+        *a.get_mut_unchecked_tokened::<i32>(1, &token).unwrap() += 40;
+        assert!(a.get_mut_unchecked_tokened::<u8>(0, &token).is_none());
+    }
+    assert_eq!(*a.get::<i32>().unwrap(), [1, 42]);
+}
+
+#[test]
+fn archetype_user_data() {
+    let mut world = World::new();
+    world.spawn((1,));
+    let a = world.archetypes().nth(1).unwrap();
+    assert_eq!(a.user_data(), 0);
+    a.set_user_data(42);
+    assert_eq!(world.archetypes().nth(1).unwrap().user_data(), 42);
+}
+
+#[test]
+#[should_panic(expected = "i32 requested more than once")]
+fn columnar_access_mut_duplicate() {
+    let mut world = World::new();
+    world.spawn(("abc", 123));
+    let a = world.archetypes().nth(1).unwrap();
+    let _ = a.get_mut2::<i32, i32>();
+}
+
 #[test]
 fn empty_entity_ref() {
     let mut world = World::new();